@@ -1,6 +1,6 @@
-use std::{env, fs, path::PathBuf};
+use std::{env, fs, num::NonZeroUsize, path::PathBuf};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::error::Error;
 
@@ -10,7 +10,7 @@ const DEFAULT_CONFIG_PATH: &str = "config.toml";
 // Environment variable to specify configuration file.
 const CONFIG_ENV_VAR: &str = "RITE_CONFIG";
 
-#[derive(Deserialize)]
+#[derive(Clone, Hash, Deserialize)]
 pub struct Config {
     pub url: String,
     pub title: String,
@@ -25,15 +25,193 @@ pub struct Config {
     pub posts_src_scripts: Option<Vec<String>>,
     pub posts_embed_scripts: Option<PathBuf>,
     pub posts_noscript: Option<String>,
+    // Posts per page of the posts index and tag pages. One page if not set.
+    pub paginate_by: Option<NonZeroUsize>,
+
+    // Feed author, falls back to `title`.
+    pub author: Option<String>,
+    // Feed description, falls back to "`title` posts".
+    pub description: Option<String>,
+    // Feed language, as a language tag such as `en-us`.
+    #[serde(default = "default_language")]
+    pub language: String,
+    // Most posts included in a feed, newest first. All if not set.
+    pub feed_limit: Option<usize>,
+    #[serde(default = "default_feeds")]
+    pub feeds: Vec<Feed>,
+    #[serde(default)]
+    pub feed_content: FeedContent,
+
+    #[serde(default)]
+    pub markdown: MarkdownOptions,
+
+    // Rules for `robots.txt`. Everything is allowed if not set.
+    #[serde(default = "default_robots")]
+    pub robots: Vec<RobotsRule>,
+}
+
+/// Feed formats for posts.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Feed {
+    /// RSS 2.0, `rss.xml`.
+    Rss,
+    /// Atom, `atom.xml`.
+    Atom,
+    /// JSON Feed 1.1, `feed.json`.
+    Json,
+}
+
+impl Feed {
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Feed::Rss => "rss.xml",
+            Feed::Atom => "atom.xml",
+            Feed::Json => "feed.json",
+        }
+    }
+
+    /// File name of the feed for posts tagged `tag`, in the tags directory.
+    pub fn tag_file_name(&self, tag: &str) -> String {
+        match self {
+            Feed::Rss => format!("{}.xml", tag),
+            Feed::Atom => format!("{}.atom.xml", tag),
+            Feed::Json => format!("{}.json", tag),
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Feed::Rss => "application/rss+xml",
+            Feed::Atom => "application/atom+xml",
+            Feed::Json => "application/feed+json",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feed::Rss => "RSS",
+            Feed::Atom => "Atom",
+            Feed::Json => "JSON",
+        }
+    }
+}
+
+fn default_language() -> String {
+    "en-us".to_string()
+}
+
+fn default_feeds() -> Vec<Feed> {
+    vec![Feed::Rss, Feed::Atom, Feed::Json]
+}
+
+/// Markdown extensions, the `[markdown]` section.
+/// Posts can override any of them in their header.
+#[derive(Clone, Copy, Hash, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarkdownOptions {
+    pub tables: bool,
+    pub strikethrough: bool,
+    pub tasklists: bool,
+    // Curly quotes, dashes and ellipses.
+    pub smart_punctuation: bool,
+    // `{#id .class}` after a heading.
+    pub heading_attributes: bool,
+    // Whether headings link to themselves.
+    pub heading_anchors: bool,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        Self {
+            tables: true,
+            strikethrough: true,
+            tasklists: true,
+            smart_punctuation: false,
+            heading_attributes: false,
+            heading_anchors: false,
+        }
+    }
+}
+
+impl MarkdownOptions {
+    /// These options, with any set in `overrides` replaced.
+    pub fn with(self, overrides: &MarkdownOverrides) -> Self {
+        Self {
+            tables: overrides.tables.unwrap_or(self.tables),
+            strikethrough: overrides.strikethrough.unwrap_or(self.strikethrough),
+            tasklists: overrides.tasklists.unwrap_or(self.tasklists),
+            smart_punctuation: overrides
+                .smart_punctuation
+                .unwrap_or(self.smart_punctuation),
+            heading_attributes: overrides
+                .heading_attributes
+                .unwrap_or(self.heading_attributes),
+            heading_anchors: overrides.heading_anchors.unwrap_or(self.heading_anchors),
+        }
+    }
+}
+
+/// Markdown options set by a post, in its `markdown` header field.
+#[derive(Clone, Default, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarkdownOverrides {
+    pub tables: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub tasklists: Option<bool>,
+    pub smart_punctuation: Option<bool>,
+    pub heading_attributes: Option<bool>,
+    pub heading_anchors: Option<bool>,
+}
+
+/// Group of `robots.txt` rules for one user agent.
+#[derive(Clone, Hash, Deserialize)]
+pub struct RobotsRule {
+    #[serde(default = "default_user_agent")]
+    pub user_agent: String,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub disallow: Vec<String>,
+}
+
+fn default_user_agent() -> String {
+    "*".to_string()
+}
+
+fn default_robots() -> Vec<RobotsRule> {
+    vec![RobotsRule {
+        user_agent: default_user_agent(),
+        allow: Vec::new(),
+        disallow: Vec::new(),
+    }]
+}
+
+/// What feed entries carry besides the post summary.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedContent {
+    /// The full post.
+    #[default]
+    Full,
+    /// Nothing, readers follow the link for the rest.
+    Summary,
+}
+
+/// Options given on the command line, rather than in the configuration file.
+#[derive(Clone, Copy, Default)]
+pub struct BuildOptions {
+    /// Render draft posts.
+    pub drafts: bool,
+    /// Render posts dated after the build time.
+    pub future: bool,
 }
 
 /// Read the configuration file.
 /// Defaults to `config.toml` unless overridden by the `RITE_CONFIG`
 /// environment variable.
 pub fn read_config() -> Result<Config, Error> {
-    let path: PathBuf = env::var(CONFIG_ENV_VAR)
-        .unwrap_or(DEFAULT_CONFIG_PATH.into())
-        .into();
+    let path = config_path();
 
     if !path.exists() {
         return Err(Error::MissingConfig(path));
@@ -44,3 +222,10 @@ pub fn read_config() -> Result<Config, Error> {
     config.url = config.url.trim_end_matches('/').to_string();
     Ok(config)
 }
+
+/// Path of the configuration file, see [`read_config`].
+pub fn config_path() -> PathBuf {
+    env::var(CONFIG_ENV_VAR)
+        .unwrap_or(DEFAULT_CONFIG_PATH.into())
+        .into()
+}
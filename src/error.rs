@@ -8,10 +8,22 @@ pub enum Error {
     Askama(askama::Error),
     Syntect(syntect::Error),
     SyntectLoad(PathBuf, syntect::LoadingError),
-    ChronoParse(chrono::format::ParseError),
-    ReadPostHeader(PathBuf, toml::de::Error),
+    Signal(ctrlc::Error),
+    ReadPost(PathBuf, io::Error),
+    ReadPostHeader(PathBuf, String),
+    InvalidPost(PathBuf, usize, String),
+    InvalidPage(PathBuf, String),
+    InvalidDate(String),
     ReadConfig(PathBuf, toml::de::Error),
+    ReadManifest(PathBuf, toml::de::Error),
     MissingConfig(PathBuf),
+    PostExists(PathBuf),
+    DuplicatePost(String),
+    InvalidPostName(String, String),
+    DuplicateTag(String, String),
+    InvalidTag(String, String),
+    DuplicateSeries(String, String),
+    InvalidSeries(String, String),
 }
 
 impl fmt::Display for Error {
@@ -28,20 +40,45 @@ impl fmt::Display for Error {
                     e
                 )
             }
-            Error::ChronoParse(e) => write!(f, "failed to parse datetime: {}", e),
+            Error::Signal(e) => write!(f, "failed to handle interrupts: {}", e),
+            Error::ReadPost(path, e) => {
+                write!(f, "failed to read post from {}: {}", path.display(), e)
+            }
             Error::ReadPostHeader(path, e) => write!(
                 f,
                 "failed to read post header from {}: {}",
                 path.display(),
                 e
             ),
+            Error::InvalidPost(path, line, e) => {
+                write!(f, "invalid post {}, line {}: {}", path.display(), line, e)
+            }
+            Error::InvalidPage(path, e) => write!(f, "invalid page {}: {}", path.display(), e),
+            Error::InvalidDate(e) => write!(f, "{}", e),
             Error::ReadConfig(path, e) => write!(
                 f,
                 "failed to read configuration from {}: {}",
                 path.display(),
                 e
             ),
+            Error::ReadManifest(path, e) => write!(
+                f,
+                "failed to read build manifest from {}: {}",
+                path.display(),
+                e
+            ),
             Error::MissingConfig(path) => write!(f, "config file {} not found", path.display()),
+            Error::PostExists(path) => write!(f, "post {} already exists", path.display()),
+            Error::DuplicatePost(name) => write!(f, "more than one post is named '{}'", name),
+            Error::InvalidPostName(name, e) => write!(f, "invalid post name '{}': {}", name, e),
+            Error::DuplicateTag(a, b) => {
+                write!(f, "tags '{}' and '{}' would share the same page", a, b)
+            }
+            Error::InvalidTag(tag, e) => write!(f, "invalid tag '{}': {}", tag, e),
+            Error::DuplicateSeries(a, b) => {
+                write!(f, "series '{}' and '{}' would share the same page", a, b)
+            }
+            Error::InvalidSeries(name, e) => write!(f, "invalid series '{}': {}", name, e),
         }
     }
 }
@@ -58,8 +95,8 @@ impl From<askama::Error> for Error {
     }
 }
 
-impl From<chrono::format::ParseError> for Error {
-    fn from(e: chrono::format::ParseError) -> Self {
-        Self::ChronoParse(e)
+impl From<ctrlc::Error> for Error {
+    fn from(e: ctrlc::Error) -> Self {
+        Self::Signal(e)
     }
 }
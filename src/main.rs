@@ -1,19 +1,35 @@
-use std::{env, fs, path::Path};
+use std::{
+    env, fs,
+    io::{self, IsTerminal, Write},
+    path::Path,
+    process::Command,
+};
 
 mod config;
 mod error;
+mod manifest;
 mod post;
 mod render;
+mod serve;
 mod templates;
 mod util;
+mod watch;
 
-use config::Config;
+use config::{BuildOptions, Config};
 use error::Error;
-use post::Posts;
+use manifest::Manifest;
+use post::{NewPost, Posts};
 use render::Renderer;
 
 const STATIC_FILES_PATH: &str = "static";
 
+const COMMANDS: &[&str] = &[
+    "build [--drafts] [--future]",
+    "post [title] [--title <title>] [--tag <tag>]... [--date <date>] [--draft] [--edit]",
+    "serve [port]",
+    "watch [--drafts] [--future]",
+];
+
 fn main() {
     match wrap_error() {
         Ok(()) => (),
@@ -29,15 +45,27 @@ fn wrap_error() -> Result<(), Error> {
     let config = config::read_config()?;
 
     match handle_args(&args) {
-        Args::Build => build(&config)?,
-        Args::Post => new_post(&config)?,
+        Args::Build(options) => build(&config, options)?,
+        Args::Post(post, edit) => new_post(&config, post, edit)?,
+        Args::Serve(port) => serve::serve(&config, port)?,
+        Args::Watch(options) => {
+            build(&config, options)?;
+            watch::watch(config, |config, changes| {
+                watch::rebuild(config, options, changes)
+            })
+        }
         Args::Missing => {
-            eprintln!("usage: {} build | post", &args[0]);
+            usage(&args[0]);
             std::process::exit(1);
         }
         Args::Unknown(s) => {
             eprintln!("unknown command '{}'.", s);
-            eprintln!("usage: {} build | post", &args[0]);
+            usage(&args[0]);
+            std::process::exit(1);
+        }
+        Args::Invalid(s) => {
+            eprintln!("invalid argument '{}'.", s);
+            usage(&args[0]);
             std::process::exit(1);
         }
     }
@@ -47,24 +75,92 @@ fn wrap_error() -> Result<(), Error> {
 
 fn handle_args(args: &[String]) -> Args {
     match args.get(1).map(|s| &s[..]) {
-        Some("b" | "build") => Args::Build,
-        Some("p" | "post") => Args::Post,
+        Some("b" | "build") => match build_options(&args[2..]) {
+            Ok(options) => Args::Build(options),
+            Err(s) => Args::Invalid(s),
+        },
+        Some("p" | "post") => match post_options(&args[2..]) {
+            Ok((post, edit)) => Args::Post(post, edit),
+            Err(s) => Args::Invalid(s),
+        },
+        Some("s" | "serve") => match args.get(2) {
+            Some(port) => match port.parse() {
+                Ok(port) => Args::Serve(port),
+                Err(_) => Args::Invalid(port.into()),
+            },
+            None => Args::Serve(serve::DEFAULT_PORT),
+        },
+        Some("w" | "watch") => match build_options(&args[2..]) {
+            Ok(options) => Args::Watch(options),
+            Err(s) => Args::Invalid(s),
+        },
         Some(s) => Args::Unknown(s.into()),
         None => Args::Missing,
     }
 }
 
+fn build_options(flags: &[String]) -> Result<BuildOptions, String> {
+    let mut options = BuildOptions::default();
+    for flag in flags {
+        match &flag[..] {
+            "--drafts" => options.drafts = true,
+            "--future" => options.future = true,
+            _ => return Err(flag.clone()),
+        }
+    }
+    Ok(options)
+}
+
+// Header fields for `post`, and whether to open it in an editor.
+fn post_options(args: &[String]) -> Result<(NewPost, bool), String> {
+    let mut post = NewPost::default();
+    let mut edit = false;
+    let mut words = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--title" => post.title = args.next().ok_or_else(|| arg.clone())?.clone(),
+            "--tag" => post
+                .tags
+                .push(args.next().ok_or_else(|| arg.clone())?.clone()),
+            "--date" => {
+                let date = args.next().ok_or_else(|| arg.clone())?;
+                post.date = Some(date.parse().map_err(|_| date.clone())?);
+            }
+            "--draft" => post.draft = true,
+            "--edit" => edit = true,
+            _ if arg.starts_with("--") => return Err(arg.clone()),
+            _ => words.push(&arg[..]),
+        }
+    }
+    if !words.is_empty() {
+        post.title = words.join(" ");
+    }
+    Ok((post, edit))
+}
+
+fn usage(program: &str) {
+    eprintln!("usage:");
+    for command in COMMANDS {
+        eprintln!("  {} {}", program, command);
+    }
+}
+
 enum Args {
-    Build,
-    Post,
+    Build(BuildOptions),
+    Post(NewPost, bool),
+    Serve(u16),
+    Watch(BuildOptions),
 
     // Errors.
     Missing,
     Unknown(String),
+    Invalid(String),
 }
 
-fn build(config: &Config) -> Result<(), Error> {
-    if config.build_root.exists() {
+fn build(config: &Config, options: BuildOptions) -> Result<(), Error> {
+    // Without a manifest, nothing in the build directory can be trusted.
+    if config.build_root.exists() && !Manifest::exists(config) {
         println!(
             ">> removing build directory '{}'",
             config.build_root.display()
@@ -72,24 +168,70 @@ fn build(config: &Config) -> Result<(), Error> {
         fs::remove_dir_all(&config.build_root)?;
     }
 
-    println!(
-        ">> creating build directory '{}'",
-        config.build_root.display()
-    );
-    fs::create_dir_all(&config.build_root)?;
+    if !config.build_root.exists() {
+        println!(
+            ">> creating build directory '{}'",
+            config.build_root.display()
+        );
+        fs::create_dir_all(&config.build_root)?;
+    }
+
+    let manifest = Manifest::load(config)?;
+    match render_site(config, options, &manifest) {
+        Ok(()) => manifest.finish(true),
+        Err(e) => {
+            // Record whatever was written before the failure, so that it is
+            // not taken to be up to date by the next build.
+            manifest.finish(false)?;
+            Err(e)
+        }
+    }
+}
+
+fn render_site(config: &Config, options: BuildOptions, manifest: &Manifest) -> Result<(), Error> {
+    copy_static(config, manifest)?;
+    let renderer = Renderer::new(config, options, manifest)?;
+    renderer.render()
+}
 
+fn copy_static(config: &Config, manifest: &Manifest) -> Result<(), Error> {
     println!(">> copying static files");
     let static_dir = Path::new(STATIC_FILES_PATH);
-    util::copy_static(static_dir, &config.build_root.join(static_dir))?;
+    util::copy_static(static_dir, &config.build_root.join(static_dir), manifest)
+}
 
-    let renderer = Renderer::new(config)?;
-    renderer.render()?;
+fn new_post(config: &Config, mut post: NewPost, edit: bool) -> Result<(), Error> {
+    // Ask for anything missing, if there is someone to ask.
+    if io::stdin().is_terminal() {
+        if post.title.is_empty() {
+            post.title = prompt("title")?;
+        }
+        if post.tags.is_empty() {
+            post.tags = prompt("tags (comma separated)")?
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect();
+        }
+    }
 
+    let mut posts = Posts::new(&config.posts)?;
+    let path = posts.create_post(post)?;
+
+    if edit {
+        // `EDITOR` may include arguments, such as `code --wait`.
+        let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".into());
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+        Command::new(program).args(words).arg(&path).status()?;
+    }
     Ok(())
 }
 
-fn new_post(config: &Config) -> Result<(), Error> {
-    let mut posts = Posts::new(&config.posts)?;
-    posts.create_post()?;
-    Ok(())
+fn prompt(field: &str) -> Result<String, Error> {
+    print!("{}: ", field);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim().to_string())
 }
@@ -0,0 +1,454 @@
+/// Build manifest, used to skip outputs whose inputs have not changed.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    hash::Hash,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, error::Error, templates, util};
+
+// Directory beside `config.build_root` holding the manifest and render cache
+// of each build directory, so that neither is deployed with the site.
+const MANIFEST_DIR: &str = ".rite";
+const MANIFEST_FILE: &str = "manifest.toml";
+const CACHE_DIR: &str = "cache";
+
+/// Record of every output written by a build, keyed by its path relative to
+/// the build directory, along with a hash of the inputs that produced it.
+pub struct Manifest {
+    build_root: PathBuf,
+    // Hash of the configuration, syntax theme, templates and version, and of
+    // the compiler, which the hashes of inputs depend on.
+    // Previous outputs are only reused if this matches.
+    stamp: String,
+    previous: ManifestFile,
+    outputs: Mutex<BTreeMap<String, String>>,
+    cached: Mutex<BTreeSet<String>>,
+    // Used to name cache entries while they are written.
+    temp: AtomicUsize,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ManifestFile {
+    stamp: String,
+    outputs: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// Whether a previous build left a manifest for `config.build_root`.
+    pub fn exists(config: &Config) -> bool {
+        manifest_path(&config.build_root).exists()
+    }
+
+    /// Load the previous build's manifest, if any.
+    pub fn load(config: &Config) -> Result<Self, Error> {
+        // The configuration only has the theme's path. If it cannot be read,
+        // rendering reports that.
+        let theme = config
+            .syntax_theme
+            .as_ref()
+            .and_then(|path| fs::read(path).ok());
+        let stamp = util::hash(&(
+            env!("CARGO_PKG_VERSION"),
+            env!("RITE_RUSTC_VERSION"),
+            templates::SOURCES,
+            config,
+            theme,
+        ));
+        let path = manifest_path(&config.build_root);
+        let previous = if path.exists() {
+            let contents = fs::read_to_string(&path)?;
+            toml::from_str(&contents).map_err(|e| Error::ReadManifest(path, e))?
+        } else {
+            ManifestFile::default()
+        };
+
+        // Rendered markdown depends on the configuration too.
+        let cache_dir = cache_dir(&config.build_root);
+        if previous.stamp != stamp && cache_dir.exists() {
+            fs::remove_dir_all(&cache_dir)?;
+        }
+        fs::create_dir_all(&cache_dir)?;
+
+        Ok(Self {
+            build_root: config.build_root.clone(),
+            stamp,
+            previous,
+            outputs: Mutex::default(),
+            cached: Mutex::default(),
+            temp: AtomicUsize::new(0),
+        })
+    }
+
+    /// Write the output of `render` to `dest`, unless `inputs` hash the same
+    /// as in the previous build and `dest` still exists.
+    /// Any missing parent directories are created.
+    /// Returns whether `dest` was written.
+    pub fn write(
+        &self,
+        dest: &Path,
+        inputs: &(impl Hash + ?Sized),
+        render: impl FnOnce() -> Result<String, Error>,
+    ) -> Result<bool, Error> {
+        let hash = util::hash(inputs);
+        let written = !self.unchanged(dest, &hash);
+        if written {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(dest, render()?)?;
+        }
+        self.record(dest, hash);
+        Ok(written)
+    }
+
+    /// Copy `src` to `dest`, unless its contents are unchanged.
+    /// Returns whether `dest` was written.
+    pub fn copy(&self, src: &Path, dest: &Path) -> Result<bool, Error> {
+        let hash = util::hash(&fs::read(src)?);
+        let written = !self.unchanged(dest, &hash);
+        if written {
+            fs::copy(src, dest)?;
+        }
+        self.record(dest, hash);
+        Ok(written)
+    }
+
+    /// Cache the output of `render` for `input` between builds.
+    /// Safe to call for the same `input` from several threads at once.
+    pub fn cached(
+        &self,
+        input: &(impl Hash + ?Sized),
+        render: impl FnOnce() -> Result<String, Error>,
+    ) -> Result<String, Error> {
+        let key = util::hash(input);
+        let path = cache_dir(&self.build_root).join(&key);
+        self.cached.lock().unwrap().insert(key);
+        if path.exists() {
+            return Ok(fs::read_to_string(path)?);
+        }
+        // Entries are renamed into place once written, so that they are never
+        // read half-written.
+        let output = render()?;
+        let temp =
+            path.with_extension(format!("{}.tmp", self.temp.fetch_add(1, Ordering::Relaxed)));
+        fs::write(&temp, &output)?;
+        fs::rename(temp, path)?;
+        Ok(output)
+    }
+
+    /// Save the manifest.
+    ///
+    /// With `prune`, the build is taken to be complete, so any outputs and
+    /// cache entries of the previous build that were not produced again are
+    /// removed. Otherwise they are kept, which is also how a failed build
+    /// should be finished, so that the outputs it did write are recorded.
+    pub fn finish(self, prune: bool) -> Result<(), Error> {
+        let mut outputs = self.outputs.into_inner().unwrap();
+        let cached = self.cached.into_inner().unwrap();
+        let stale: Vec<_> = self
+            .previous
+            .outputs
+            .iter()
+            .filter(|(path, _)| !outputs.contains_key(*path))
+            .collect();
+
+        if prune {
+            if !stale.is_empty() {
+                println!(">> pruning stale outputs");
+            }
+            for (path, _) in stale {
+                let path = self.build_root.join(path);
+                if path.exists() {
+                    println!("  -- '{}'", path.display());
+                    fs::remove_file(path)?;
+                }
+            }
+            for entry in cache_dir(&self.build_root).read_dir()? {
+                let path = entry?.path();
+                let unused = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_none_or(|name| !cached.contains(name));
+                if unused {
+                    fs::remove_file(path)?;
+                }
+            }
+        } else if self.previous.stamp == self.stamp {
+            for (path, hash) in stale {
+                outputs.insert(path.clone(), hash.clone());
+            }
+        }
+
+        let file = ManifestFile {
+            stamp: self.stamp,
+            outputs,
+        };
+        let contents = toml::to_string(&file).expect("Failed to serialize build manifest");
+        fs::write(manifest_path(&self.build_root), contents)?;
+        Ok(())
+    }
+
+    // Whether `dest` is unchanged since the previous build, given the hash
+    // of its inputs.
+    fn unchanged(&self, dest: &Path, hash: &str) -> bool {
+        self.previous.stamp == self.stamp
+            && self
+                .previous
+                .outputs
+                .get(&self.key(dest))
+                .map(String::as_str)
+                == Some(hash)
+            && dest.exists()
+    }
+
+    // Record the hash for `dest`, once it is up to date. Outputs that failed
+    // to render keep their previous hash, if any, so they are tried again.
+    fn record(&self, dest: &Path, hash: String) {
+        self.outputs.lock().unwrap().insert(self.key(dest), hash);
+    }
+
+    fn key(&self, dest: &Path) -> String {
+        dest.strip_prefix(&self.build_root)
+            .unwrap_or(dest)
+            .display()
+            .to_string()
+    }
+}
+
+// Directory holding the manifest and render cache for `build_root`.
+fn state_dir(build_root: &Path) -> PathBuf {
+    let parent = build_root.parent().unwrap_or(Path::new("."));
+    parent.join(MANIFEST_DIR).join(util::hash(build_root))
+}
+
+fn manifest_path(build_root: &Path) -> PathBuf {
+    state_dir(build_root).join(MANIFEST_FILE)
+}
+
+fn cache_dir(build_root: &Path) -> PathBuf {
+    state_dir(build_root).join(CACHE_DIR)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn config(build_root: &Path) -> Config {
+        let mut config: Config = toml::from_str(
+            r#"
+            url = "https://example.com"
+            title = "Example"
+            content = "content"
+            posts = "posts"
+            build_root = "public"
+            posts_root = "posts"
+            "#,
+        )
+        .unwrap();
+        config.build_root = build_root.into();
+        config
+    }
+
+    fn render(s: &str) -> impl FnOnce() -> Result<String, Error> + '_ {
+        move || Ok(s.into())
+    }
+
+    fn unreachable() -> Result<String, Error> {
+        panic!("rendered an unchanged output")
+    }
+
+    #[test]
+    fn write_skips_unchanged_inputs() {
+        let dir = TempDir::new().unwrap();
+        let config = config(&dir.path().join("public"));
+        let dest = config.build_root.join("page.html");
+
+        let manifest = Manifest::load(&config).unwrap();
+        assert!(manifest.write(&dest, "input", render("first")).unwrap());
+        manifest.finish(true).unwrap();
+
+        let manifest = Manifest::load(&config).unwrap();
+        assert!(!manifest.write(&dest, "input", unreachable).unwrap());
+        manifest.finish(true).unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "first");
+    }
+
+    #[test]
+    fn write_renders_changed_inputs() {
+        let dir = TempDir::new().unwrap();
+        let config = config(&dir.path().join("public"));
+        let dest = config.build_root.join("posts").join("page.html");
+
+        let manifest = Manifest::load(&config).unwrap();
+        assert!(manifest.write(&dest, "input", render("first")).unwrap());
+        manifest.finish(true).unwrap();
+
+        let manifest = Manifest::load(&config).unwrap();
+        assert!(manifest.write(&dest, "changed", render("second")).unwrap());
+        manifest.finish(true).unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "second");
+    }
+
+    #[test]
+    fn write_renders_missing_outputs() {
+        let dir = TempDir::new().unwrap();
+        let config = config(&dir.path().join("public"));
+        let dest = config.build_root.join("page.html");
+
+        let manifest = Manifest::load(&config).unwrap();
+        manifest.write(&dest, "input", render("first")).unwrap();
+        manifest.finish(true).unwrap();
+        fs::remove_file(&dest).unwrap();
+
+        let manifest = Manifest::load(&config).unwrap();
+        assert!(manifest.write(&dest, "input", render("first")).unwrap());
+        assert!(dest.exists());
+    }
+
+    #[test]
+    fn config_changes_render_everything() {
+        let dir = TempDir::new().unwrap();
+        let mut config = config(&dir.path().join("public"));
+        let dest = config.build_root.join("page.html");
+
+        let manifest = Manifest::load(&config).unwrap();
+        manifest.write(&dest, "input", render("first")).unwrap();
+        manifest.finish(true).unwrap();
+
+        config.title = "Changed".into();
+        let manifest = Manifest::load(&config).unwrap();
+        assert!(manifest.write(&dest, "input", render("second")).unwrap());
+    }
+
+    #[test]
+    fn finish_prunes_outputs_not_written() {
+        let dir = TempDir::new().unwrap();
+        let config = config(&dir.path().join("public"));
+        let kept = config.build_root.join("kept.html");
+        let removed = config.build_root.join("removed.html");
+
+        let manifest = Manifest::load(&config).unwrap();
+        manifest.write(&kept, "kept", render("kept")).unwrap();
+        manifest
+            .write(&removed, "removed", render("removed"))
+            .unwrap();
+        manifest.finish(true).unwrap();
+
+        let manifest = Manifest::load(&config).unwrap();
+        manifest.write(&kept, "kept", unreachable).unwrap();
+        manifest.finish(true).unwrap();
+        assert!(kept.exists());
+        assert!(!removed.exists());
+    }
+
+    #[test]
+    fn finish_keeps_outputs_not_written_without_pruning() {
+        let dir = TempDir::new().unwrap();
+        let config = config(&dir.path().join("public"));
+        let kept = config.build_root.join("kept.html");
+        let other = config.build_root.join("other.html");
+
+        let manifest = Manifest::load(&config).unwrap();
+        manifest.write(&kept, "kept", render("kept")).unwrap();
+        manifest.write(&other, "other", render("other")).unwrap();
+        manifest.finish(true).unwrap();
+
+        let manifest = Manifest::load(&config).unwrap();
+        manifest.write(&kept, "kept", unreachable).unwrap();
+        manifest.finish(false).unwrap();
+        assert!(other.exists());
+
+        // It is still recorded, so it is not rendered again.
+        let manifest = Manifest::load(&config).unwrap();
+        assert!(!manifest.write(&other, "other", unreachable).unwrap());
+    }
+
+    #[test]
+    fn failed_builds_record_what_they_wrote() {
+        let dir = TempDir::new().unwrap();
+        let config = config(&dir.path().join("public"));
+        let written = config.build_root.join("written.html");
+        let failed = config.build_root.join("failed.html");
+
+        let manifest = Manifest::load(&config).unwrap();
+        manifest.write(&written, "input", render("first")).unwrap();
+        manifest.write(&failed, "input", render("first")).unwrap();
+        manifest.finish(true).unwrap();
+
+        // A build that writes one output, then fails on the next.
+        let manifest = Manifest::load(&config).unwrap();
+        assert!(manifest
+            .write(&written, "changed", render("second"))
+            .unwrap());
+        let result = manifest.write(&failed, "changed", || Err(Error::InvalidDate("".into())));
+        assert!(result.is_err());
+        manifest.finish(false).unwrap();
+
+        // Going back to the first inputs brings back the first outputs.
+        let manifest = Manifest::load(&config).unwrap();
+        assert!(manifest.write(&written, "input", render("first")).unwrap());
+        assert!(!manifest.write(&failed, "input", unreachable).unwrap());
+        manifest.finish(true).unwrap();
+        assert_eq!(fs::read_to_string(&written).unwrap(), "first");
+        assert_eq!(fs::read_to_string(&failed).unwrap(), "first");
+    }
+
+    #[test]
+    fn cached_reuses_output_between_builds() {
+        let dir = TempDir::new().unwrap();
+        let config = config(&dir.path().join("public"));
+
+        let manifest = Manifest::load(&config).unwrap();
+        assert_eq!(manifest.cached("input", render("first")).unwrap(), "first");
+        manifest.finish(true).unwrap();
+
+        let manifest = Manifest::load(&config).unwrap();
+        assert_eq!(manifest.cached("input", unreachable).unwrap(), "first");
+        manifest.finish(true).unwrap();
+    }
+
+    #[test]
+    fn finish_prunes_unused_cache_entries() {
+        let dir = TempDir::new().unwrap();
+        let config = config(&dir.path().join("public"));
+
+        let manifest = Manifest::load(&config).unwrap();
+        manifest.cached("input", render("first")).unwrap();
+        manifest.finish(true).unwrap();
+
+        Manifest::load(&config).unwrap().finish(true).unwrap();
+
+        let manifest = Manifest::load(&config).unwrap();
+        assert_eq!(
+            manifest.cached("input", render("second")).unwrap(),
+            "second"
+        );
+    }
+
+    #[test]
+    fn cached_from_several_threads() {
+        let dir = TempDir::new().unwrap();
+        let config = config(&dir.path().join("public"));
+        let manifest = Manifest::load(&config).unwrap();
+
+        let inputs = vec!["input"; 64];
+        let outputs = util::par_map(&inputs, |input| {
+            manifest.cached(*input, render("output")).unwrap()
+        });
+        assert!(outputs.iter().all(|output| output == "output"));
+        manifest.finish(true).unwrap();
+
+        let cache = cache_dir(&config.build_root).read_dir().unwrap().count();
+        assert_eq!(cache, 1);
+    }
+}
@@ -1,97 +1,289 @@
 use std::{
-    fs,
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt, fs,
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
 };
 
-use crate::error::Error;
+use crate::{config::MarkdownOverrides, error::Error, util};
 
 use chrono::prelude::*;
-use serde::{Deserialize, Serialize};
+use pulldown_cmark::{Event, Parser, Tag};
+use serde::{
+    de::{self, value::MapAccessDeserializer, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 use toml::value::{Date as TomlDate, Datetime as TomlDatetime};
 
-// Used to separate the "top" of the post, to be used as a summary.
-// Currently not used for anything, maybe a tooltip or summary page later.
+// Used to separate the "top" of the post, to be used as its summary.
 const TOP_TAG: &str = "<!-- top -->";
 
 pub struct Posts {
     root: PathBuf,
     posts: Vec<Post>,
-    tags: Vec<String>,
+    // Sorted by name.
+    tags: Vec<TagEntry>,
+    // Sorted by name.
+    series: Vec<SeriesEntry>,
+}
+
+/// A tag and the posts that have it.
+#[derive(Hash)]
+pub struct TagEntry {
+    pub name: String,
+    // Used in place of the name in paths and URLs.
+    pub slug: String,
+    // Indices into the posts, so newest first too.
+    indices: Vec<usize>,
+}
+
+/// A series and its parts.
+#[derive(Hash)]
+pub struct SeriesEntry {
+    pub name: String,
+    // Used in place of the name in paths and URLs.
+    pub slug: String,
+    // Indices into the posts, in reading order.
+    indices: Vec<usize>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Hash)]
 pub struct Post {
     pub name: String,
     pub title: String,
-    pub date: NaiveDate,
+    pub date: DateTime<FixedOffset>,
+    // When the post was last revised, if after publishing.
+    pub updated: Option<DateTime<FixedOffset>>,
     pub tags: Vec<String>,
+    pub series: Option<Series>,
+    pub draft: bool,
+    // Whether to show a table of contents.
+    pub toc: bool,
+    // Replaces the configured markdown options.
+    pub markdown: MarkdownOverrides,
     pub content: String,
-    pub top: Option<usize>,
+    // Markdown until rendered, like `content`.
+    pub summary: String,
+}
+
+/// Series a post is part of.
+#[derive(Clone, Hash, Serialize, Deserialize)]
+pub struct Series {
+    pub name: String,
+    // Position in the series, which is otherwise ordered by date.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i64>,
 }
 
+// A series is given either by name alone or with its order.
 #[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SeriesHeader {
+    Name(String),
+    Series(Series),
+}
+
+/// Header fields of a post to be created.
+#[derive(Default)]
+pub struct NewPost {
+    pub title: String,
+    pub tags: Vec<String>,
+    // Today, if not given.
+    pub date: Option<TomlDatetime>,
+    pub draft: bool,
+}
+
+// Keys this does not know are ignored, since posts imported from other
+// generators come with their own.
+#[derive(Default, Serialize, Deserialize)]
 struct PostHeader {
+    #[serde(default)]
     title: String,
-    date: TomlDatetime,
+    // Required, but only checked once the header has been read, so that
+    // the error can point at it.
+    #[serde(default, deserialize_with = "deserialize_date")]
+    date: Option<TomlDatetime>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_date",
+        skip_serializing_if = "Option::is_none"
+    )]
+    updated: Option<TomlDatetime>,
+    #[serde(default)]
     tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    series: Option<SeriesHeader>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    toc: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    markdown: Option<MarkdownOverrides>,
+    // Overrides the file name in the post's URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    slug: Option<String>,
+    // Overrides the summary taken from the content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
 }
 
 impl Posts {
     pub fn new(root: &Path) -> Result<Self, Error> {
         let posts = collect_posts(root)?;
-        let tags = collect_tags(&posts);
+        let tags = posts.iter().flat_map(|post| &post.tags);
+        check_slugs(tags, Error::DuplicateTag, Error::InvalidTag)?;
+        let series = posts
+            .iter()
+            .filter_map(|post| Some(&post.series.as_ref()?.name));
+        check_slugs(series, Error::DuplicateSeries, Error::InvalidSeries)?;
         Ok(Self {
             root: root.into(),
+            tags: collect_tags(&posts),
+            series: collect_series(&posts),
             posts,
-            tags,
         })
     }
 
-    pub fn root(&self) -> &Path {
-        &self.root
-    }
+    /// Create a new post named after its title, or numbered if the title
+    /// has nothing to name it by. Existing files are never overwritten.
+    /// Returns the path of the new post.
+    pub fn create_post(&mut self, new: NewPost) -> Result<PathBuf, Error> {
+        let mut name = util::slugify(&new.title);
+        if name.is_empty() {
+            let mut next = self.posts.len();
+            while self
+                .root
+                .join(next.to_string())
+                .with_extension("md")
+                .exists()
+            {
+                next += 1;
+            }
+            name = next.to_string();
+        }
 
-    pub fn create_post(&mut self) -> Result<Post, Error> {
-        let next = self.posts.len();
-        let date = Utc::today().naive_utc();
+        check_post_name(&name).map_err(|e| Error::InvalidPostName(name.clone(), e.into()))?;
 
-        let post = Post {
-            name: next.to_string(),
-            title: String::new(),
-            date,
-            tags: Vec::new(),
-            content: String::new(),
-            top: None,
-        };
+        let post_path = self.root.join(&name).with_extension("md");
+        if post_path.exists() {
+            return Err(Error::PostExists(post_path));
+        }
+        let existing = self.posts.iter().flat_map(|post| &post.tags);
+        check_slugs(
+            existing.chain(&new.tags),
+            Error::DuplicateTag,
+            Error::InvalidTag,
+        )?;
 
-        let post_path = &self.root.join(&post.name).with_extension("md");
-        let header = PostHeader {
-            title: String::new(),
-            date: TomlDatetime {
+        let date = new.date.unwrap_or_else(|| {
+            let today = Utc::now().date_naive();
+            TomlDatetime {
                 date: Some(TomlDate {
-                    year: date.year() as u16,
-                    month: date.month() as u8,
-                    day: date.day() as u8,
+                    year: today.year() as u16,
+                    month: today.month() as u8,
+                    day: today.day() as u8,
                 }),
                 time: None,
                 offset: None,
-            },
-            tags: Vec::new(),
+            }
+        });
+
+        let post = Post {
+            name,
+            title: new.title.clone(),
+            date: parse_date(&date).map_err(Error::InvalidDate)?,
+            updated: None,
+            tags: new.tags.clone(),
+            series: None,
+            draft: new.draft,
+            toc: false,
+            markdown: MarkdownOverrides::default(),
+            content: String::new(),
+            summary: String::new(),
+        };
+
+        let header = PostHeader {
+            title: new.title,
+            date: Some(date),
+            updated: None,
+            tags: new.tags,
+            series: None,
+            draft: new.draft,
+            toc: false,
+            markdown: None,
+            slug: None,
+            summary: None,
         };
 
         let header = toml::to_string(&header).expect("Failed to serialize post header");
         println!("» created new post '{}'", post_path.display());
         println!("{}", header);
-        fs::write(post_path, format!("---\n{}---\n\n{}", header, TOP_TAG))?;
+        fs::write(&post_path, format!("+++\n{}+++\n\n{}", header, TOP_TAG))?;
 
-        self.posts.push(post.clone());
-        Ok(post)
+        self.posts.push(post);
+        self.tags = collect_tags(&self.posts);
+        self.series = collect_series(&self.posts);
+        Ok(post_path)
     }
 
-    pub fn tags(&self) -> &[String] {
+    /// Every tag, sorted by name.
+    pub fn tags(&self) -> &[TagEntry] {
         &self.tags
     }
+
+    /// Every tag, the most used first.
+    pub fn tags_by_count(&self) -> Vec<&TagEntry> {
+        let mut tags: Vec<_> = self.tags.iter().collect();
+        tags.sort_by(|a, b| b.count().cmp(&a.count()).then_with(|| a.name.cmp(&b.name)));
+        tags
+    }
+
+    /// Every series, sorted by name.
+    pub fn series(&self) -> &[SeriesEntry] {
+        &self.series
+    }
+
+    /// The series of the post at `index`, if it is part of one.
+    pub fn series_of(&self, index: usize) -> Option<&SeriesEntry> {
+        let name = &self.posts[index].series.as_ref()?.name;
+        let i = self
+            .series
+            .binary_search_by(|entry| entry.name.cmp(name))
+            .ok()?;
+        Some(&self.series[i])
+    }
+
+    /// Up to `limit` other posts sharing tags with the post at `index`,
+    /// those sharing the most first, then the newest.
+    pub fn related(&self, index: usize, limit: usize) -> Vec<&Post> {
+        let tags: BTreeSet<_> = self.posts[index].tags.iter().collect();
+        let mut shared = BTreeMap::<_, usize>::new();
+        for tag in tags {
+            if let Ok(t) = self.tags.binary_search_by(|entry| entry.name.cmp(tag)) {
+                for &i in &self.tags[t].indices {
+                    if i != index {
+                        *shared.entry(i).or_default() += 1;
+                    }
+                }
+            }
+        }
+        // Indices are newest first, and the sort is stable.
+        let mut related: Vec<_> = shared.into_iter().collect();
+        related.sort_by_key(|&(_, count)| Reverse(count));
+        related
+            .into_iter()
+            .take(limit)
+            .map(|(i, _)| &self.posts[i])
+            .collect()
+    }
+
+    /// Keep only the posts matching `f`, along with their tags and series.
+    pub fn retain(&mut self, f: impl FnMut(&Post) -> bool) {
+        self.posts.retain(f);
+        self.tags = collect_tags(&self.posts);
+        self.series = collect_series(&self.posts);
+    }
 }
 
 impl Deref for Posts {
@@ -108,41 +300,143 @@ impl DerefMut for Posts {
     }
 }
 
+impl TagEntry {
+    /// Number of posts with the tag.
+    pub fn count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// The posts with the tag, out of the `posts` it was collected from
+    /// or a copy of them in the same order.
+    pub fn posts<'p>(&self, posts: &'p [Post]) -> Vec<&'p Post> {
+        self.indices.iter().map(|&i| &posts[i]).collect()
+    }
+}
+
+impl SeriesEntry {
+    /// Number of parts in the series.
+    pub fn count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// The parts of the series in reading order, out of the `posts` it was
+    /// collected from.
+    pub fn posts<'p>(&self, posts: &'p [Post]) -> Vec<&'p Post> {
+        self.indices.iter().map(|&i| &posts[i]).collect()
+    }
+
+    /// Position of the post at `index` in the series, from 1.
+    pub fn part(&self, index: usize) -> Option<usize> {
+        self.indices.iter().position(|&i| i == index).map(|i| i + 1)
+    }
+}
+
 impl Post {
     fn read(root: &Path, path: &Path) -> Result<Self, Error> {
-        let contents = fs::read_to_string(root.join(path))?;
-        let header_end = contents[4..].find("---").expect("invalid post header") + 4;
-        let toml = &contents[4..header_end];
-
-        let PostHeader { title, date, tags } =
-            toml::from_str(toml).map_err(|e| Error::ReadPostHeader(path.into(), e))?;
-        let date = date.date.expect("expected TOML date");
-        let content = &contents[header_end + 5..];
-        let top = content.find(TOP_TAG);
+        let file = root.join(path);
+        let contents = fs::read_to_string(&file).map_err(|e| Error::ReadPost(file.clone(), e))?;
+        let contents = contents
+            .strip_prefix('\u{feff}')
+            .unwrap_or(&contents)
+            .replace("\r\n", "\n");
         let name = path
             .file_stem()
-            .expect("expected file name")
-            .to_str()
-            .expect("expected UTF-8")
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| Error::InvalidPost(file.clone(), 1, "file name is not UTF-8".into()))?
             .to_string();
 
+        let (header, content) = read_header(&file, &contents)?;
+        // Posts without a header have no metadata, so are dated by their file.
+        let dated = header.is_some();
+        let header = header.unwrap_or_default();
+        let PostHeader {
+            title,
+            date,
+            updated,
+            tags,
+            series,
+            draft,
+            toc,
+            markdown,
+            slug,
+            summary,
+        } = header.fields;
+        let invalid = |key: &str, e: String| {
+            let line = key_line(header.text, key, header.first_line);
+            Error::InvalidPost(file.clone(), line, e)
+        };
+
+        let date = match date {
+            Some(date) => parse_date(&date).map_err(|e| invalid("date", e))?,
+            None if dated => {
+                let e = "post header has no date";
+                return Err(Error::InvalidPost(
+                    file.clone(),
+                    header.first_line,
+                    e.into(),
+                ));
+            }
+            None => fs::metadata(&file)
+                .and_then(|metadata| metadata.modified())
+                .map(|modified| DateTime::<Utc>::from(modified).fixed_offset())
+                .map_err(|e| Error::ReadPost(file.clone(), e))?,
+        };
+        let updated = updated
+            .map(|updated| parse_date(&updated).map_err(|e| invalid("updated", e)))
+            .transpose()?;
+        let title = if title.is_empty() {
+            name.clone()
+        } else {
+            title
+        };
+        let name = match slug {
+            Some(slug) => {
+                let slug = util::slugify(&slug);
+                check_post_name(&slug).map_err(|e| invalid("slug", e.into()))?;
+                slug
+            }
+            None => {
+                let slug = util::slugify(&name);
+                check_post_name(&slug)
+                    .map_err(|e| Error::InvalidPost(file.clone(), 1, e.into()))?;
+                slug
+            }
+        };
+
         Ok(Self {
-            name,
             title,
-            date: NaiveDate::parse_from_str(&date.to_string(), "%Y-%m-%d")?,
+            name,
+            date,
+            updated,
             tags,
+            series: series.map(|series| match series {
+                SeriesHeader::Name(name) => Series { name, order: None },
+                SeriesHeader::Series(series) => series,
+            }),
+            draft,
+            toc,
+            markdown: markdown.unwrap_or_default(),
+            summary: summary.unwrap_or_else(|| summarize(content).into()),
             content: content.into(),
-            top,
         })
     }
 
-    pub fn has_tag(&self, tag: &str) -> bool {
-        self.tags.iter().any(|t| t == tag)
+    pub fn rss_date(&self) -> String {
+        self.date.to_rfc2822()
+    }
+
+    pub fn atom_date(&self) -> String {
+        self.date.to_rfc3339()
     }
 
-    pub fn rss_date(&self) -> String {
-        let dt = Utc.from_local_date(&self.date).unwrap().and_hms(0, 0, 0);
-        dt.to_rfc2822()
+    /// When the post was last revised, or published if never.
+    pub fn last_modified(&self) -> DateTime<FixedOffset> {
+        self.updated.unwrap_or(self.date)
+    }
+
+    /// Date of the latest revision, RFC 3339.
+    pub fn updated_date(&self) -> String {
+        self.last_modified().to_rfc3339()
     }
 }
 
@@ -150,25 +444,478 @@ fn collect_posts(root: &Path) -> Result<Vec<Post>, Error> {
     let mut posts = Vec::new();
     for entry in root.read_dir()? {
         let path = entry?.path();
-        if !path.is_dir() {
+        if util::is_markdown(&path) {
             if let Some(name) = path.file_name() {
                 posts.push(Post::read(root, Path::new(name))?);
             }
         }
     }
-    posts.sort_by_key(|post| format!("{}-{}", &post.date, &post.title));
+    posts.sort_by(|a, b| (a.date, &a.title).cmp(&(b.date, &b.title)));
     posts.reverse();
+
+    // Each post is written to a file named after it.
+    let mut names = HashSet::new();
+    for post in &posts {
+        if !names.insert(&post.name) {
+            return Err(Error::DuplicatePost(post.name.clone()));
+        }
+    }
     Ok(posts)
 }
 
-fn collect_tags(posts: &[Post]) -> Vec<String> {
-    let mut tags = Vec::new();
-    for post in posts {
+// The summary of a post is everything above the top tag, or its first
+// paragraph if there is nothing there.
+fn summarize(content: &str) -> &str {
+    match content.find(TOP_TAG) {
+        Some(top) if !content[..top].trim().is_empty() => content[..top].trim(),
+        _ => Parser::new(content)
+            .into_offset_iter()
+            .find_map(|(event, range)| match event {
+                Event::Start(Tag::Paragraph) => Some(&content[range]),
+                _ => None,
+            })
+            .unwrap_or_default(),
+    }
+}
+
+// A parsed post header, along with where it sits in the file.
+#[derive(Default)]
+struct Header<'a> {
+    fields: PostHeader,
+    text: &'a str,
+    // Line of the file that `text` starts on.
+    first_line: usize,
+}
+
+// Read a post's front matter, if it has any, returning it along with the
+// content after it.
+//
+// The format is told apart by the start of the post: `+++` for TOML, `---`
+// for YAML, and `{` for JSON. Posts without front matter, or with an empty
+// one, are all content.
+fn read_header<'a>(file: &Path, contents: &'a str) -> Result<(Option<Header<'a>>, &'a str), Error> {
+    let error = |e: String| Error::ReadPostHeader(file.into(), e);
+
+    if contents.starts_with('{') {
+        let mut stream = serde_json::Deserializer::from_str(contents).into_iter::<PostHeader>();
+        let header = match stream.next() {
+            Some(header) => header.map_err(|e| error(e.to_string()))?,
+            None => PostHeader::default(),
+        };
+        let end = stream.byte_offset();
+        let header = Header {
+            fields: header,
+            text: &contents[..end],
+            first_line: 1,
+        };
+        return Ok((Some(header), &contents[end..]));
+    }
+
+    let delimiter = match contents.lines().next().map(str::trim_end) {
+        Some(delimiter @ ("+++" | "---")) => delimiter,
+        _ => return Ok((None, contents)),
+    };
+    let (header, content) = split_header(file, contents, delimiter)?;
+    if header.trim().is_empty() {
+        return Ok((None, content));
+    }
+
+    // Pad the header so that errors point at the right line of the file.
+    let padded = format!("\n{}", header);
+    let parsed = if delimiter == "+++" || is_toml(header) {
+        toml::from_str(&padded).map_err(|e| e.to_string())
+    } else {
+        serde_yaml::from_str(&padded).map_err(|e| e.to_string())
+    };
+    let header = Header {
+        fields: parsed.map_err(error)?,
+        text: header,
+        first_line: 2,
+    };
+    Ok((Some(header), content))
+}
+
+// Split a post into its header, without the `delimiter` lines around it,
+// and its content.
+fn split_header<'a>(
+    file: &Path,
+    contents: &'a str,
+    delimiter: &str,
+) -> Result<(&'a str, &'a str), Error> {
+    let mut lines = contents.split_inclusive('\n');
+    let start = lines.next().map_or(0, str::len);
+    let mut end = start;
+    for line in lines {
+        if line.trim_end() == delimiter {
+            return Ok((&contents[start..end], &contents[end + line.len()..]));
+        }
+        end += line.len();
+    }
+    Err(Error::InvalidPost(
+        file.into(),
+        1,
+        format!("post header is never closed with '{}'", delimiter),
+    ))
+}
+
+// Posts from before `+++` was supported have TOML between `---` lines.
+// Their first key is set with `=` rather than `:`.
+fn is_toml(header: &str) -> bool {
+    let line = header
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .unwrap_or_default();
+    match (line.find('='), line.find(':')) {
+        (Some(eq), Some(colon)) => eq < colon,
+        (eq, _) => eq.is_some(),
+    }
+}
+
+// Line of the file on which `key` is set, given the header starts on line
+// `first`.
+fn key_line(header: &str, key: &str, first: usize) -> usize {
+    header
+        .lines()
+        .position(|line| line.trim_start().trim_start_matches('"').starts_with(key))
+        .map_or(first, |i| i + first)
+}
+
+// Dates are strings in YAML and JSON, but have their own type in TOML.
+fn deserialize_date<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<TomlDatetime>, D::Error> {
+    struct DateVisitor;
+
+    impl<'de> Visitor<'de> for DateVisitor {
+        type Value = TomlDatetime;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a date")
+        }
+
+        fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+            s.parse()
+                .or_else(|e| date_from_str(s).ok_or(e))
+                .map_err(E::custom)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            TomlDatetime::deserialize(MapAccessDeserializer::new(map))
+        }
+    }
+
+    deserializer.deserialize_any(DateVisitor).map(Some)
+}
+
+// Jekyll and others write dates as `2021-03-04 10:00:00 +0100`, which is not
+// TOML datetime syntax, so these are converted.
+fn date_from_str(s: &str) -> Option<TomlDatetime> {
+    let s = s.trim();
+    let converted = if let Ok(dt) = DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f %z") {
+        dt.to_rfc3339()
+    } else {
+        let dt = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f").ok()?;
+        dt.format("%Y-%m-%dT%H:%M:%S%.f").to_string()
+    };
+    converted.parse().ok()
+}
+
+// Post dates can be a plain date, a local datetime, or a datetime with an
+// offset. A missing time is taken to be midnight, and a missing offset UTC.
+fn parse_date(date: &TomlDatetime) -> Result<DateTime<FixedOffset>, String> {
+    if date.date.is_none() {
+        return Err(format!("date '{}' has no day", date));
+    }
+    let s = date.to_string();
+    let parsed = match (&date.time, &date.offset) {
+        (_, Some(_)) => DateTime::parse_from_rfc3339(&s),
+        (Some(_), None) => NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S%.f")
+            .map(|dt| dt.and_utc().fixed_offset()),
+        (None, None) => NaiveDate::parse_from_str(&s, "%Y-%m-%d")
+            .map(|d| d.and_time(NaiveTime::MIN).and_utc().fixed_offset()),
+    };
+    parsed.map_err(|e| format!("invalid date '{}': {}", date, e))
+}
+
+// Each post is written to a file named after it, beside the posts index.
+fn check_post_name(name: &str) -> Result<(), &'static str> {
+    match name {
+        "" => Err("no letters or digits to name its page by"),
+        "index" => Err("its page would replace the posts index"),
+        _ => Ok(()),
+    }
+}
+
+// Tags and series are told apart by their slugs once built, so those must
+// be distinct and non-empty. Errors are made with `duplicate` and `invalid`.
+fn check_slugs<'n>(
+    names: impl IntoIterator<Item = &'n String>,
+    duplicate: fn(String, String) -> Error,
+    invalid: fn(String, String) -> Error,
+) -> Result<(), Error> {
+    let mut slugs = HashMap::new();
+    for name in names {
+        let slug = util::slugify(name);
+        if slug.is_empty() {
+            let e = "no letters or digits to name its page by";
+            return Err(invalid(name.clone(), e.into()));
+        }
+        if slug == "index" {
+            let e = "its page would replace the index";
+            return Err(invalid(name.clone(), e.into()));
+        }
+        match slugs.insert(slug, name) {
+            Some(other) if other != name => return Err(duplicate(other.clone(), name.clone())),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn collect_tags(posts: &[Post]) -> Vec<TagEntry> {
+    let mut tags = BTreeMap::<_, Vec<_>>::new();
+    for (i, post) in posts.iter().enumerate() {
         for tag in &post.tags {
-            tags.push(tag.to_string());
+            let indices = tags.entry(tag.as_str()).or_default();
+            // A post listing a tag twice is still only tagged once.
+            if indices.last() != Some(&i) {
+                indices.push(i);
+            }
+        }
+    }
+    tags.into_iter()
+        .map(|(name, indices)| TagEntry {
+            name: name.to_string(),
+            slug: util::slugify(name),
+            indices,
+        })
+        .collect()
+}
+
+fn collect_series(posts: &[Post]) -> Vec<SeriesEntry> {
+    let mut series = BTreeMap::<_, Vec<_>>::new();
+    for (i, post) in posts.iter().enumerate() {
+        if let Some(Series { name, .. }) = &post.series {
+            series.entry(name.as_str()).or_default().push(i);
         }
     }
-    tags.sort_unstable();
-    tags.dedup();
-    tags
+    series
+        .into_iter()
+        .map(|(name, mut indices)| {
+            // Parts given an order come first, then the rest oldest first.
+            indices.sort_by_key(|&i| {
+                let order = posts[i].series.as_ref().and_then(|series| series.order);
+                (order.is_none(), order, Reverse(i))
+            });
+            SeriesEntry {
+                name: name.to_string(),
+                slug: util::slugify(name),
+                indices,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn read(contents: &str) -> Result<Post, Error> {
+        read_file("a-post.md", contents)
+    }
+
+    fn read_file(name: &str, contents: &str) -> Result<Post, Error> {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(name), contents).unwrap();
+        Post::read(dir.path(), Path::new(name))
+    }
+
+    fn assert_header(post: &Post) {
+        assert_eq!(post.name, "a-post");
+        assert_eq!(post.title, "A post");
+        assert_eq!(post.date.to_rfc3339(), "2023-01-05T00:00:00+00:00");
+        assert_eq!(post.tags, ["one", "two"]);
+        assert_eq!(post.content.trim(), "Some content.");
+    }
+
+    fn assert_invalid(result: Result<Post, Error>, line: usize) {
+        match result {
+            Err(Error::InvalidPost(_, l, _)) => assert_eq!(l, line),
+            Err(e) => panic!("expected an invalid post, got: {}", e),
+            Ok(_) => panic!("expected an invalid post"),
+        }
+    }
+
+    #[test]
+    fn only_markdown_files_are_posts() {
+        let dir = TempDir::new().unwrap();
+        let post = "+++\ndate = 2023-01-05\n+++\nSome content.\n";
+        fs::write(dir.path().join("a-post.md"), post).unwrap();
+        fs::write(dir.path().join(".a-post.md.swp"), [0xff, 0xfe, 0x00]).unwrap();
+        fs::write(dir.path().join("a-post.md~"), post).unwrap();
+        fs::write(dir.path().join(".hidden.md"), post).unwrap();
+        fs::write(dir.path().join("notes.txt"), post).unwrap();
+        fs::create_dir(dir.path().join("scripts.md")).unwrap();
+
+        let posts = Posts::new(dir.path()).unwrap();
+        let names: Vec<_> = posts.iter().map(|post| &post.name[..]).collect();
+        assert_eq!(names, ["a-post"]);
+    }
+
+    #[test]
+    fn create_post_names_it_after_its_title() {
+        let dir = TempDir::new().unwrap();
+        let mut posts = Posts::new(dir.path()).unwrap();
+        let new = NewPost {
+            title: "My Title".into(),
+            ..NewPost::default()
+        };
+        let path = posts.create_post(new).unwrap();
+        assert_eq!(path, dir.path().join("my-title.md"));
+        assert_eq!(Posts::new(dir.path()).unwrap()[0].title, "My Title");
+    }
+
+    #[test]
+    fn create_post_rejects_the_index() {
+        let dir = TempDir::new().unwrap();
+        let mut posts = Posts::new(dir.path()).unwrap();
+        let new = NewPost {
+            title: "Index".into(),
+            ..NewPost::default()
+        };
+        let result = posts.create_post(new);
+        assert!(matches!(result, Err(Error::InvalidPostName(..))));
+        assert!(!dir.path().join("index.md").exists());
+    }
+
+    #[test]
+    fn toml_front_matter() {
+        let post = read(
+            "+++\ntitle = \"A post\"\ndate = 2023-01-05\ntags = [\"one\", \"two\"]\n+++\n\nSome content.\n",
+        )
+        .unwrap();
+        assert_header(&post);
+    }
+
+    #[test]
+    fn toml_front_matter_between_dashes() {
+        let post = read(
+            "---\ntitle = \"A post\"\ndate = 2023-01-05\ntags = [\"one\", \"two\"]\n---\n\nSome content.\n",
+        )
+        .unwrap();
+        assert_header(&post);
+    }
+
+    #[test]
+    fn yaml_front_matter() {
+        let post =
+            read("---\ntitle: A post\ndate: 2023-01-05\ntags: [one, two]\n---\n\nSome content.\n")
+                .unwrap();
+        assert_header(&post);
+    }
+
+    #[test]
+    fn yaml_front_matter_with_jekyll_dates() {
+        let date = |date: &str| {
+            let post = read(&format!("---\ntitle: A post\ndate: {}\n---\n", date)).unwrap();
+            post.date.to_rfc3339()
+        };
+        assert_eq!(
+            date("2021-03-04 10:00:00 +0100"),
+            "2021-03-04T10:00:00+01:00"
+        );
+        assert_eq!(
+            date("2021-03-04 10:00:00.5 -0500"),
+            "2021-03-04T10:00:00.500-05:00"
+        );
+        assert_eq!(date("2021-03-04 10:00:00"), "2021-03-04T10:00:00+00:00");
+        assert_eq!(
+            date("\"2021-03-04 10:00:00 +0100\""),
+            "2021-03-04T10:00:00+01:00"
+        );
+    }
+
+    #[test]
+    fn json_front_matter() {
+        let post = read(
+            "{\"title\": \"A post\", \"date\": \"2023-01-05\", \"tags\": [\"one\", \"two\"]}\n\nSome content.\n",
+        )
+        .unwrap();
+        assert_header(&post);
+    }
+
+    #[test]
+    fn crlf_and_byte_order_mark() {
+        let post = read(
+            "\u{feff}+++\r\ntitle = \"A post\"\r\ndate = 2023-01-05\r\ntags = [\"one\", \"two\"]\r\n+++\r\n\r\nSome content.\r\n",
+        )
+        .unwrap();
+        assert_header(&post);
+    }
+
+    #[test]
+    fn no_front_matter() {
+        let post = read("Some content.\n").unwrap();
+        assert_eq!(post.name, "a-post");
+        assert_eq!(post.title, "a-post");
+        assert!(post.tags.is_empty());
+        assert_eq!(post.content, "Some content.\n");
+    }
+
+    #[test]
+    fn invalid_date_reports_its_line() {
+        assert_invalid(read("+++\ntitle = \"A post\"\ndate = 2023-02-30\n+++\n"), 3);
+        assert_invalid(read("---\ntitle: A post\n\ndate: 2023-02-30\n---\n"), 4);
+        assert_invalid(
+            read("{\n\"title\": \"A post\",\n\"date\": \"2023-02-30\"\n}\n"),
+            3,
+        );
+    }
+
+    #[test]
+    fn missing_date() {
+        assert_invalid(read("+++\ntitle = \"A post\"\n+++\n"), 2);
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let post = read(
+            "---\nlayout: post\ntitle: A post\ndate: 2023-01-05\ncategories: [one]\nauthor: Someone\n---\n",
+        )
+        .unwrap();
+        assert_eq!(post.title, "A post");
+
+        // A misspelt date is still missed.
+        assert_invalid(read("+++\ntitle = \"A post\"\ndat = 2023-01-05\n+++\n"), 2);
+    }
+
+    #[test]
+    fn unknown_markdown_option() {
+        let result = read("+++\ndate = 2023-01-05\nmarkdown = { table = false }\n+++\n");
+        assert!(matches!(result, Err(Error::ReadPostHeader(..))));
+    }
+
+    #[test]
+    fn file_name_is_slugged() {
+        let post = read_file("Café Ünï!.md", "Some content.\n").unwrap();
+        assert_eq!(post.name, "cafe-uni");
+        assert_eq!(post.title, "Café Ünï!");
+        assert_invalid(read_file("!!!.md", "Some content.\n"), 1);
+        assert_invalid(read_file("Index.md", "Some content.\n"), 1);
+    }
+
+    #[test]
+    fn slug_overrides_file_name() {
+        let post = read("+++\ndate = 2023-01-05\nslug = \"Other Post\"\n+++\n").unwrap();
+        assert_eq!(post.name, "other-post");
+        assert_invalid(read("+++\ndate = 2023-01-05\nslug = \"!!!\"\n+++\n"), 3);
+    }
+
+    #[test]
+    fn unclosed_front_matter() {
+        assert_invalid(read("+++\ntitle = \"A post\"\ndate = 2023-01-05\n"), 1);
+    }
 }
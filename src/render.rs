@@ -1,24 +1,40 @@
-use std::{fs, path::Path};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
+    hash::Hash,
+    path::{Path, PathBuf},
+};
 
 use askama::Template;
-use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag};
+use chrono::{DateTime, Datelike, FixedOffset, Month, Utc};
+use pulldown_cmark::{
+    escape::escape_html, html, CodeBlockKind, CowStr, Event, HeadingLevel, Options, Parser, Tag,
+};
 use syntect::{
     highlighting::{Theme, ThemeSet},
     parsing::SyntaxSet,
 };
 
 use crate::{
-    config::Config,
+    config::{BuildOptions, Config, Feed, FeedContent, MarkdownOptions},
     error::Error,
-    post::{Post, Posts},
+    manifest::Manifest,
+    post::{Post, Posts, TagEntry},
     templates::{
-        ContentTemplate, IndexTemplate, NotFoundTemplate, PostTemplate, PostsTemplate, RssTemplate,
-        Script, ScriptsTemplate, TagTemplate, TagsTemplate,
+        ArchiveMonth, ArchivePeriodTemplate, ArchiveTemplate, ArchiveYear, AtomTemplate,
+        ContentTemplate, IndexTemplate, JsonFeed, JsonFeedAuthor, JsonFeedItem, NotFoundTemplate,
+        Pagination, PostTemplate, PostsTemplate, RobotsTemplate, RssTemplate, Script,
+        ScriptsTemplate, SearchIndex, SearchPost, SearchTemplate, SeriesListTemplate, SeriesNav,
+        SeriesTemplate, SitemapTemplate, SitemapUrl, TagTemplate, TagsTemplate,
     },
+    util,
 };
 
 // File stems in the `config.content` directory reserved for specific templates.
-const RESERVED_CONTENT_NAMES: &[&str] = &["index", "posts", "404"];
+const RESERVED_CONTENT_NAMES: &[&str] = &["index", "posts", "search", "404"];
+
+// Most related posts listed under a post.
+const RELATED_POSTS: usize = 5;
 
 // Fallback if no theme is specified in `config.toml`.
 //
@@ -29,11 +45,17 @@ const DEFAULT_SYNTAX_THEME: &str = "base16-mocha.dark";
 /// Renderer for the site's content.
 pub struct Renderer<'a> {
     config: &'a Config,
+    options: BuildOptions,
+    manifest: &'a Manifest,
     markdown: Markdown,
 }
 
 impl<'a> Renderer<'a> {
-    pub fn new(config: &'a Config) -> Result<Self, Error> {
+    pub fn new(
+        config: &'a Config,
+        options: BuildOptions,
+        manifest: &'a Manifest,
+    ) -> Result<Self, Error> {
         let theme = match &config.syntax_theme {
             Some(path) => {
                 ThemeSet::get_theme(path).map_err(|e| Error::SyntectLoad(path.clone(), e))?
@@ -45,6 +67,8 @@ impl<'a> Renderer<'a> {
         };
         Ok(Self {
             config,
+            options,
+            manifest,
             markdown: Markdown::with_theme(theme),
         })
     }
@@ -53,16 +77,17 @@ impl<'a> Renderer<'a> {
     pub fn render(&self) -> Result<(), Error> {
         self.render_index()?;
         self.render_not_found()?;
+        self.render_search()?;
         self.render_content()?;
-
-        let posts = Posts::new(&self.config.posts)?;
-        self.render_posts_and_tags(posts)?;
-
+        let mut posts = self.published_posts()?;
+        self.render_posts(&mut posts)?;
+        self.render_sitemap(&posts)?;
+        self.render_robots()?;
         Ok(())
     }
 
-    // Render index page.
-    fn render_index(&self) -> Result<(), Error> {
+    /// Render index page.
+    pub fn render_index(&self) -> Result<(), Error> {
         let src = self.config.content.join("index.md");
         let content = &self.content_or_blank(&src)?;
         let template = IndexTemplate {
@@ -72,15 +97,14 @@ impl<'a> Renderer<'a> {
         };
 
         let dest = self.config.build_root.join("index.html");
-        let render = template.render()?;
-
-        println!(">> creating '{}'", dest.display());
-        fs::write(dest, render)?;
+        if self.write(&dest, content, &template)? {
+            println!(">> creating '{}'", dest.display());
+        }
         Ok(())
     }
 
-    // Render not found page.
-    fn render_not_found(&self) -> Result<(), Error> {
+    /// Render not found page.
+    pub fn render_not_found(&self) -> Result<(), Error> {
         let src = self.config.content.join("404.md");
         let message = &self.content_or_blank(&src)?;
         let template = NotFoundTemplate {
@@ -89,42 +113,171 @@ impl<'a> Renderer<'a> {
         };
 
         let dest = self.config.build_root.join("404.html");
-        let render = template.render()?;
+        if self.write(&dest, message, &template)? {
+            println!(">> creating '{}'", dest.display());
+        }
+        Ok(())
+    }
 
-        println!(">> creating '{}'", dest.display());
-        fs::write(dest, render)?;
+    /// Render search page.
+    pub fn render_search(&self) -> Result<(), Error> {
+        let src = self.config.content.join("search.md");
+        let description = &self.content_or_blank(&src)?;
+        let template = SearchTemplate {
+            title: &self.config.title,
+            posts_root: &self.config.posts_root,
+            description,
+        };
+
+        let dest = self.config.build_root.join("search.html");
+        if self.write(&dest, description, &template)? {
+            println!(">> creating '{}'", dest.display());
+        }
         Ok(())
     }
 
-    // Render additional content pages.
-    fn render_content(&self) -> Result<(), Error> {
+    /// Render additional content pages.
+    pub fn render_content(&self) -> Result<(), Error> {
         println!(">> creating additional content");
+        let pages = self.content_pages()?;
+        let written = util::par_map(&pages, |page| self.render_content_page(page));
+        for dest in written {
+            if let Some(dest) = dest? {
+                println!("  -- '{}'", dest.display());
+            }
+        }
+        Ok(())
+    }
+
+    // The additional content pages, sorted to keep the log in the same
+    // order from build to build.
+    fn content_pages(&self) -> Result<Vec<ContentPage>, Error> {
+        let mut paths = Vec::new();
         for entry in self.config.content.read_dir()? {
             let path = entry?.path();
-            if !path.is_dir() {
+            if util::is_markdown(&path) {
                 // TODO. Walk recursively?
-                // Also maybe clean it up.
-                let name = path.file_stem().unwrap().to_str().unwrap();
-                if !RESERVED_CONTENT_NAMES.contains(&name) {
-                    let content = fs::read_to_string(&path)?;
-                    let content = &self.markdown.render_html(&content)?;
-                    let template = ContentTemplate {
-                        title: &self.config.title,
-                        name,
-                        content,
-                    };
-                    let dest = self.config.build_root.join(name).with_extension("html");
-                    let render = template.render()?;
-                    println!("  -- '{}'", dest.display());
-                    fs::write(dest, render)?;
-                }
+                paths.push(path);
             }
         }
+        paths.sort();
+
+        let mut pages: Vec<ContentPage> = Vec::new();
+        for path in paths {
+            let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+            let slug = util::slugify(&name);
+            if RESERVED_CONTENT_NAMES.contains(&&slug[..]) {
+                continue;
+            }
+            if slug.is_empty() {
+                let e = "file name has no letters or digits to name its page by";
+                return Err(Error::InvalidPage(path, e.into()));
+            }
+            if let Some(other) = pages.iter().find(|page| page.slug == slug) {
+                let e = format!("it would share its page with {}", other.path.display());
+                return Err(Error::InvalidPage(path, e));
+            }
+            pages.push(ContentPage { path, name, slug });
+        }
+        Ok(pages)
+    }
+
+    // Render a single content page, returning its destination if written.
+    fn render_content_page(&self, page: &ContentPage) -> Result<Option<PathBuf>, Error> {
+        let content = fs::read_to_string(&page.path)?;
+        let content = &self.render_markdown(&content, self.config.markdown)?;
+        let template = ContentTemplate {
+            title: &self.config.title,
+            name: &page.name,
+            content,
+        };
+        let dest = self
+            .config
+            .build_root
+            .join(&page.slug)
+            .with_extension("html");
+        Ok(self.write(&dest, content, &template)?.then_some(dest))
+    }
+
+    /// Render the sitemap of every page, listing `posts`.
+    pub fn render_sitemap(&self, posts: &Posts) -> Result<(), Error> {
+        let posts_url = self.posts_url();
+        let latest = |posts: &[&Post]| {
+            let latest = posts.iter().map(|post| post.last_modified()).max();
+            latest.map(|date| date.to_rfc3339())
+        };
+        let all: Vec<_> = posts.iter().collect();
+
+        let mut urls = vec![SitemapUrl {
+            loc: format!("{}/", self.config.url),
+            lastmod: None,
+        }];
+        for page in self.content_pages()? {
+            urls.push(SitemapUrl {
+                loc: format!("{}/{}.html", self.config.url, page.slug),
+                lastmod: None,
+            });
+        }
+        urls.push(SitemapUrl {
+            loc: format!("{}/", posts_url),
+            lastmod: latest(&all),
+        });
+        for post in posts.iter() {
+            urls.push(SitemapUrl {
+                loc: format!("{}/{}.html", posts_url, post.name),
+                lastmod: Some(post.updated_date()),
+            });
+        }
+        urls.push(SitemapUrl {
+            loc: format!("{}/tags/", posts_url),
+            lastmod: latest(&all),
+        });
+        for tag in posts.tags() {
+            urls.push(SitemapUrl {
+                loc: format!("{}/tags/{}.html", posts_url, tag.slug),
+                lastmod: latest(&tag.posts(posts)),
+            });
+        }
+
+        let dest = self.config.build_root.join("sitemap.xml");
+        let template = SitemapTemplate { urls: &urls };
+        if self.write(&dest, &urls, &template)? {
+            println!(">> creating '{}'", dest.display());
+        }
         Ok(())
     }
 
-    // Render posts, tags, and RSS feed.
-    fn render_posts_and_tags(&self, mut posts: Posts) -> Result<(), Error> {
+    /// Render the crawler rules.
+    pub fn render_robots(&self) -> Result<(), Error> {
+        let template = RobotsTemplate {
+            rules: &self.config.robots,
+            sitemap_url: &format!("{}/sitemap.xml", self.config.url),
+        };
+        let dest = self.config.build_root.join("robots.txt");
+        // Only depends on the configuration.
+        if self.write(&dest, &(), &template)? {
+            println!(">> creating '{}'", dest.display());
+        }
+        Ok(())
+    }
+
+    /// Posts to be built, leaving out drafts and future posts unless asked
+    /// for.
+    pub fn published_posts(&self) -> Result<Posts, Error> {
+        let mut posts = Posts::new(&self.config.posts)?;
+        if !self.options.drafts {
+            posts.retain(|post| !post.draft);
+        }
+        if !self.options.future {
+            let now = Utc::now();
+            posts.retain(|post| post.date <= now);
+        }
+        Ok(posts)
+    }
+
+    /// Render posts, tags, and feeds.
+    /// The markdown of `posts` is replaced by the HTML it renders to.
+    pub fn render_posts(&self, posts: &mut Posts) -> Result<(), Error> {
         let posts_dir = self.config.build_root.join(&self.config.posts_root);
         if !posts_dir.exists() {
             println!(
@@ -134,31 +287,75 @@ impl<'a> Renderer<'a> {
             fs::create_dir_all(&posts_dir)?;
         }
 
+        let rendered = util::par_map(posts, |post| {
+            let options = self.config.markdown.with(&post.markdown);
+            let content = self.render_markdown(&post.content, options)?;
+            let summary = self.render_markdown(&post.summary, options)?;
+            // Built from the markdown, so before it is replaced.
+            let toc = post.toc.then(|| self.markdown.toc(&post.content, options));
+            Ok::<_, Error>((content, summary, toc))
+        });
+        let mut tocs = Vec::new();
+        for (post, rendered) in posts.iter_mut().zip(rendered) {
+            let toc;
+            (post.content, post.summary, toc) = rendered?;
+            tocs.push(toc);
+        }
+
         // Create posts index.
         let posts_src = self.config.content.join("posts.md");
         let description = &self.content_or_blank(&posts_src)?;
-        let posts_template = PostsTemplate {
-            title: &self.config.title,
-            description,
-            posts: &posts,
-        };
-
-        let posts_dest = posts_dir.join("index.html");
-        let posts_render = posts_template.render()?;
-
-        println!(">> creating '{}'", posts_dest.display());
-        fs::write(posts_dest, posts_render)?;
+        let posts_root = &self.config.posts_root;
+        for page in self.paginate(posts, posts_root, &posts_root.join("index.html")) {
+            let posts_template = PostsTemplate {
+                title: &self.config.title,
+                feeds: &self.config.feeds,
+                posts_root,
+                description,
+                posts: page.items,
+                pagination: &page.pagination,
+            };
+            let inputs = (description, page.items, &page.pagination);
+            if self.write(&page.dest, &inputs, &posts_template)? {
+                println!(">> creating '{}'", page.dest.display());
+            }
+        }
 
         let scripts = self.get_post_scripts()?;
 
-        for post in posts.iter_mut() {
-            post.content = self.markdown.render_html(&post.content)?;
-            println!("  -- rendering post '{}'", post.name);
-            self.render_post(post, &scripts)?;
+        let indices: Vec<_> = (0..posts.len()).collect();
+        let written = util::par_map(&indices, |&i| {
+            self.render_post(posts, i, tocs[i].as_deref(), &scripts)
+        });
+        for (post, written) in posts.iter().zip(written) {
+            if written? {
+                println!("  -- rendering post '{}'", post.name);
+            }
+        }
+
+        // Feeds are read away from the site, so their links have to be absolute.
+        let feed_posts: Vec<_> = posts
+            .iter()
+            .map(|post| {
+                let url = format!("{}/{}.html", self.posts_url(), post.name);
+                Post {
+                    content: util::absolute_urls(&post.content, &self.config.url, &url),
+                    summary: util::absolute_urls(&post.summary, &self.config.url, &url),
+                    ..post.clone()
+                }
+            })
+            .collect();
+        let all: Vec<_> = feed_posts.iter().collect();
+        for &feed in &self.config.feeds {
+            if self.render_feed(feed, None, &all)? {
+                println!(">> rendering {}", feed.name());
+            }
         }
 
-        println!(">> rendering RSS");
-        self.render_rss(&posts)?;
+        let search_dest = posts_dir.join("search.json");
+        if self.render_search_index(&search_dest, posts)? {
+            println!(">> creating '{}'", search_dest.display());
+        }
 
         let tags_dir = posts_dir.join("tags");
         if !tags_dir.exists() {
@@ -166,9 +363,8 @@ impl<'a> Renderer<'a> {
             fs::create_dir_all(&tags_dir)?;
         }
 
-        let tags: Vec<_> = posts.tags().iter().map(|tag| tag.as_ref()).collect();
-
         // Create tags index.
+        let tags = posts.tags_by_count();
         let tags_template = TagsTemplate {
             title: &self.config.title,
             posts_root: &self.config.posts_root,
@@ -176,19 +372,153 @@ impl<'a> Renderer<'a> {
         };
 
         let tags_dest = tags_dir.join("index.html");
-        let tags_render = tags_template.render()?;
+        if self.write(&tags_dest, &tags, &tags_template)? {
+            println!(">> creating '{}'", tags_dest.display());
+        }
+
+        let tags = posts.tags();
+        let written = util::par_map(tags, |tag| self.render_tag(tag, posts, &feed_posts));
+        for (tag, written) in tags.iter().zip(written) {
+            if written? {
+                println!("  -- rendering tag '{}'", tag.name);
+            }
+        }
+
+        self.render_series(posts)?;
+        self.render_archive(posts)
+    }
+
+    // Render the series index and a page for every series.
+    fn render_series(&self, posts: &Posts) -> Result<(), Error> {
+        let series_dir = self
+            .config
+            .build_root
+            .join(&self.config.posts_root)
+            .join("series");
+        let template = SeriesListTemplate {
+            title: &self.config.title,
+            posts_root: &self.config.posts_root,
+            series: posts.series(),
+        };
+        let dest = series_dir.join("index.html");
+        if self.write(&dest, posts.series(), &template)? {
+            println!(">> creating '{}'", dest.display());
+        }
+
+        for series in posts.series() {
+            let parts = series.posts(posts);
+            let template = SeriesTemplate {
+                title: &self.config.title,
+                posts_root: &self.config.posts_root,
+                name: &series.name,
+                posts: &parts,
+            };
+            let dest = series_dir.join(format!("{}.html", series.slug));
+            if self.write(&dest, &(&series.name, &parts), &template)? {
+                println!("  -- rendering series '{}'", series.name);
+            }
+        }
+        Ok(())
+    }
+
+    // Render the archive overview and a page for every year and month.
+    fn render_archive(&self, posts: &Posts) -> Result<(), Error> {
+        let mut periods = BTreeMap::<_, BTreeMap<_, Vec<_>>>::new();
+        for post in posts.iter() {
+            let date = post.date.date_naive();
+            periods
+                .entry(date.year())
+                .or_default()
+                .entry(date.month())
+                .or_default()
+                .push(post);
+        }
 
-        println!(">> creating '{}'", tags_dest.display());
-        fs::write(tags_dest, tags_render)?;
+        let archive_dir = self
+            .config
+            .build_root
+            .join(&self.config.posts_root)
+            .join("archive");
+        let years: Vec<_> = periods
+            .iter()
+            .rev()
+            .map(|(&year, months)| ArchiveYear {
+                name: year.to_string(),
+                path: year.to_string(),
+                count: months.values().map(Vec::len).sum(),
+                months: months
+                    .iter()
+                    .rev()
+                    .map(|(&month, posts)| ArchiveMonth {
+                        name: format!("{} {}", Month::try_from(month as u8).unwrap().name(), year),
+                        path: format!("{}/{:02}", year, month),
+                        count: posts.len(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let template = ArchiveTemplate {
+            title: &self.config.title,
+            posts_root: &self.config.posts_root,
+            years: &years,
+        };
+        let dest = archive_dir.join("index.html");
+        if self.write(&dest, &years, &template)? {
+            println!(">> creating '{}'", dest.display());
+        }
+
+        for (year, months) in years.iter().zip(periods.values().rev()) {
+            let year_posts: Vec<_> = months.values().rev().flatten().copied().collect();
+            let dest = archive_dir.join(&year.path).join("index.html");
+            self.render_archive_period(&dest, &year.name, &year.months, &year_posts)?;
+            for (month, month_posts) in year.months.iter().zip(months.values().rev()) {
+                let dest = archive_dir.join(&month.path).join("index.html");
+                self.render_archive_period(&dest, &month.name, &[], month_posts)?;
+            }
+        }
+        Ok(())
+    }
 
-        for tag in &tags {
-            println!("  -- rendering tag '{}'", tag);
-            self.render_tag(tag, &posts)?;
+    fn render_archive_period(
+        &self,
+        dest: &Path,
+        name: &str,
+        months: &[ArchiveMonth],
+        posts: &[&Post],
+    ) -> Result<(), Error> {
+        let template = ArchivePeriodTemplate {
+            title: &self.config.title,
+            posts_root: &self.config.posts_root,
+            name,
+            months,
+            posts,
+        };
+        if self.write(dest, &(name, months, posts), &template)? {
+            println!("  -- rendering archive '{}'", name);
         }
         Ok(())
     }
 
-    fn render_post(&self, post: &Post, scripts: &ScriptsTemplate) -> Result<(), Error> {
+    // Render the post at `index`, linking to its neighbours in `posts`.
+    fn render_post(
+        &self,
+        posts: &Posts,
+        index: usize,
+        toc: Option<&str>,
+        scripts: &ScriptsTemplate,
+    ) -> Result<bool, Error> {
+        let post = &posts[index];
+        // Posts are newest first.
+        let prev = posts.get(index + 1);
+        let next = index.checked_sub(1).map(|i| &posts[i]);
+        let related = posts.related(index, RELATED_POSTS);
+        let series = posts.series_of(index).map(|series| SeriesNav {
+            name: &series.name,
+            slug: &series.slug,
+            part: series.part(index).unwrap(),
+            parts: series.posts(posts),
+        });
         let dest = self
             .config
             .build_root
@@ -198,54 +528,304 @@ impl<'a> Renderer<'a> {
         let template = PostTemplate {
             title: &self.config.title,
             posts_root: &self.config.posts_root,
+            feeds: &self.config.feeds,
             post,
+            prev,
+            next,
+            related: &related,
+            series,
+            toc,
             scripts,
         };
-        let render = template.render()?;
-        fs::write(dest, render)?;
-        Ok(())
+        // Only the links to the other posts show up on the page.
+        let link = |post: &Post| (post.name.clone(), post.title.clone());
+        let links = (
+            prev.map(link),
+            next.map(link),
+            related.iter().map(|post| link(post)).collect::<Vec<_>>(),
+            template.series.as_ref().map(|series| {
+                let parts: Vec<_> = series.parts.iter().map(|post| link(post)).collect();
+                (series.name, series.part, parts)
+            }),
+        );
+        self.write(&dest, &(post, toc, scripts, links), &template)
     }
 
-    fn render_rss(&self, posts: &Posts) -> Result<(), Error> {
+    // Render `feed` for `posts`, either every post or, with `tag`,
+    // only those tagged with it.
+    fn render_feed(
+        &self,
+        feed: Feed,
+        tag: Option<&TagEntry>,
+        posts: &[&Post],
+    ) -> Result<bool, Error> {
+        let posts = match self.config.feed_limit {
+            Some(limit) => &posts[..limit.min(posts.len())],
+            None => posts,
+        };
+        let posts_url = self.posts_url();
+        let site = &self.config.title;
+        let (title, description, link, path) = match tag {
+            Some(tag) => (
+                format!("{} | {}", site, tag.name),
+                format!("{} posts tagged {}", site, tag.name),
+                format!("{}/tags/{}.html", posts_url, tag.slug),
+                format!("tags/{}", feed.tag_file_name(&tag.slug)),
+            ),
+            None => (
+                site.clone(),
+                self.config
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| format!("{} posts", site)),
+                format!("{}/", posts_url),
+                feed.file_name().to_string(),
+            ),
+        };
+        let scope = FeedScope {
+            title,
+            description,
+            link,
+            feed_url: format!("{}/{}", posts_url, path),
+            // Keep the feed unchanged until the posts are.
+            updated: posts
+                .iter()
+                .map(|post| post.last_modified())
+                .max()
+                .unwrap_or_else(|| Utc::now().fixed_offset()),
+        };
         let dest = self
             .config
             .build_root
             .join(&self.config.posts_root)
-            .join("rss")
-            .with_extension("xml");
+            .join(path);
+        match feed {
+            Feed::Rss => self.render_rss(&dest, &scope, posts),
+            Feed::Atom => self.render_atom(&dest, &scope, posts),
+            Feed::Json => self.render_json_feed(&dest, &scope, posts),
+        }
+    }
+
+    fn render_rss(&self, dest: &Path, scope: &FeedScope, posts: &[&Post]) -> Result<bool, Error> {
         let template = RssTemplate {
-            title: &self.config.title,
-            posts_url: &format!("{}/{}", self.config.url, self.config.posts_root.display()),
-            description: &format!("{} posts", self.config.title),
+            title: &scope.title,
+            link: &scope.link,
+            feed_url: &scope.feed_url,
+            posts_url: &self.posts_url(),
+            description: &scope.description,
+            language: &self.config.language,
+            author: self.config.author.as_deref(),
+            last_build_date: &scope.updated.to_rfc2822(),
+            full_content: self.config.feed_content == FeedContent::Full,
             posts,
         };
-        let render = template.render()?;
-        fs::write(dest, render)?;
-        Ok(())
+        self.write(dest, posts, &template)
     }
 
-    fn render_tag(&self, tag: &str, posts: &Posts) -> Result<(), Error> {
-        let dest = self
-            .config
-            .build_root
-            .join(&self.config.posts_root)
-            .join("tags")
-            .join(tag)
-            .with_extension("html");
-        let template = TagTemplate {
-            title: &self.config.title,
-            name: tag,
+    fn render_atom(&self, dest: &Path, scope: &FeedScope, posts: &[&Post]) -> Result<bool, Error> {
+        let template = AtomTemplate {
+            title: &scope.title,
+            link: &scope.link,
+            feed_url: &scope.feed_url,
+            posts_url: &self.posts_url(),
+            language: &self.config.language,
+            author: self.config.author.as_ref().unwrap_or(&self.config.title),
+            updated: &scope.updated.to_rfc3339(),
+            full_content: self.config.feed_content == FeedContent::Full,
             posts,
         };
-        let render = template.render()?;
-        fs::write(dest, render)?;
-        Ok(())
+        self.write(dest, posts, &template)
+    }
+
+    fn render_json_feed(
+        &self,
+        dest: &Path,
+        scope: &FeedScope,
+        posts: &[&Post],
+    ) -> Result<bool, Error> {
+        let posts_url = self.posts_url();
+        let full_content = self.config.feed_content == FeedContent::Full;
+        let items = posts
+            .iter()
+            .map(|post| {
+                let url = format!("{}/{}.html", posts_url, post.name);
+                JsonFeedItem {
+                    id: url.clone(),
+                    url,
+                    title: &post.title,
+                    summary: util::html_to_text(&post.summary),
+                    content_html: if full_content {
+                        &post.content
+                    } else {
+                        &post.summary
+                    },
+                    date_published: post.atom_date(),
+                    date_modified: post.updated.map(|_| post.updated_date()),
+                    tags: &post.tags,
+                }
+            })
+            .collect();
+        let feed = JsonFeed {
+            version: "https://jsonfeed.org/version/1.1",
+            title: &scope.title,
+            home_page_url: &scope.link,
+            feed_url: &scope.feed_url,
+            description: &scope.description,
+            language: &self.config.language,
+            authors: [JsonFeedAuthor {
+                name: self.config.author.as_ref().unwrap_or(&self.config.title),
+            }],
+            items,
+        };
+        self.manifest.write(dest, posts, || {
+            Ok(serde_json::to_string_pretty(&feed).expect("Failed to serialize JSON feed"))
+        })
+    }
+
+    // Index every word of the posts' titles, tags and rendered content.
+    fn render_search_index(&self, dest: &Path, posts: &Posts) -> Result<bool, Error> {
+        let mut terms = BTreeMap::<_, Vec<_>>::new();
+        for (i, post) in posts.iter().enumerate() {
+            let text = format!(
+                "{} {} {}",
+                post.title,
+                post.tags.join(" "),
+                util::html_to_text(&post.content)
+            );
+            let words: BTreeSet<_> = text
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .map(str::to_lowercase)
+                .collect();
+            for word in words {
+                terms.entry(word).or_default().push(i);
+            }
+        }
+        let index = SearchIndex {
+            posts: posts
+                .iter()
+                .map(|post| SearchPost {
+                    title: &post.title,
+                    url: format!("/{}/{}.html", self.config.posts_root.display(), post.name),
+                    date: post.date.date_naive().to_string(),
+                    tags: &post.tags,
+                })
+                .collect(),
+            terms,
+        };
+        self.manifest.write(dest, &posts[..], || {
+            Ok(serde_json::to_string(&index).expect("Failed to serialize search index"))
+        })
+    }
+
+    // Full base URL for the posts.
+    fn posts_url(&self) -> String {
+        format!("{}/{}", self.config.url, self.config.posts_root.display())
+    }
+
+    // Render the page and feeds for `tag`, returning whether any were written.
+    fn render_tag(
+        &self,
+        tag: &TagEntry,
+        posts: &Posts,
+        feed_posts: &[Post],
+    ) -> Result<bool, Error> {
+        let tags_root = self.config.posts_root.join("tags");
+        let first = tags_root.join(format!("{}.html", tag.slug));
+        let tagged = tag.posts(posts);
+        let mut written = false;
+        for page in self.paginate(&tagged, &tags_root.join(&tag.slug), &first) {
+            let template = TagTemplate {
+                title: &self.config.title,
+                feeds: &self.config.feeds,
+                posts_root: &self.config.posts_root,
+                name: &tag.name,
+                slug: &tag.slug,
+                posts: page.items,
+                pagination: &page.pagination,
+            };
+            let inputs = (&tag.name, page.items, &page.pagination);
+            written |= self.write(&page.dest, &inputs, &template)?;
+        }
+        let tagged = tag.posts(feed_posts);
+        for &feed in &self.config.feeds {
+            written |= self.render_feed(feed, Some(tag), &tagged)?;
+        }
+        Ok(written)
+    }
+
+    // Split `items` into pages of `config.paginate_by`.
+    // The first page goes to `first` and the rest to `dir/page/N/index.html`,
+    // both relative to the build root.
+    fn paginate<'p, T>(&self, items: &'p [T], dir: &Path, first: &Path) -> Vec<Page<'p, T>> {
+        let mut chunks: Vec<_> = match self.config.paginate_by {
+            Some(size) => items.chunks(size.get()).collect(),
+            None => vec![items],
+        };
+        if chunks.is_empty() {
+            chunks.push(items);
+        }
+
+        let pages = chunks.len();
+        let url = |page: usize| {
+            if page == 1 {
+                let first = first.display().to_string();
+                format!("/{}", first.trim_end_matches("index.html"))
+            } else {
+                format!("/{}/page/{}/", dir.display(), page)
+            }
+        };
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, items)| {
+                let page = i + 1;
+                let dest = if page == 1 {
+                    self.config.build_root.join(first)
+                } else {
+                    self.config
+                        .build_root
+                        .join(dir)
+                        .join("page")
+                        .join(page.to_string())
+                        .join("index.html")
+                };
+                Page {
+                    items,
+                    dest,
+                    pagination: Pagination {
+                        page,
+                        pages,
+                        prev: (page > 1).then(|| url(page - 1)),
+                        next: (page < pages).then(|| url(page + 1)),
+                    },
+                }
+            })
+            .collect()
+    }
+
+    // Render `template` to `dest`, unless `inputs` are unchanged since the
+    // last build. Returns whether `dest` was written.
+    fn write(
+        &self,
+        dest: &Path,
+        inputs: &(impl Hash + ?Sized),
+        template: &impl Template,
+    ) -> Result<bool, Error> {
+        self.manifest.write(dest, inputs, || Ok(template.render()?))
+    }
+
+    // Markdown to HTML, reusing the output of previous builds.
+    fn render_markdown(&self, content: &str, options: MarkdownOptions) -> Result<String, Error> {
+        self.manifest.cached(&(options, content), || {
+            self.markdown.render_html(content, options)
+        })
     }
 
     fn content_or_blank(&self, path: &Path) -> Result<String, Error> {
         Ok(if path.exists() {
             let content = fs::read_to_string(path)?;
-            self.markdown.render_html(&content)?
+            self.render_markdown(&content, self.config.markdown)?
         } else {
             String::new()
         })
@@ -253,28 +833,22 @@ impl<'a> Renderer<'a> {
 
     fn get_post_scripts(&self) -> Result<ScriptsTemplate, Error> {
         let mut scripts = Vec::new();
-        match &self.config.posts_embed_scripts {
-            Some(path) => {
-                for entry in path.read_dir()? {
-                    let path = entry?.path();
-                    if !path.is_dir() {
-                        let contents = fs::read_to_string(&path)?;
-                        scripts.push(Script::Embed { contents });
-                    }
+        if let Some(path) = &self.config.posts_embed_scripts {
+            for entry in path.read_dir()? {
+                let path = entry?.path();
+                if !path.is_dir() && !util::is_ignored(&path) {
+                    let contents = fs::read_to_string(&path)?;
+                    scripts.push(Script::Embed { contents });
                 }
             }
-            None => (),
         }
 
-        match &self.config.posts_src_scripts {
-            Some(srcs) => {
-                for src in srcs {
-                    scripts.push(Script::Src {
-                        src: src.to_string(),
-                    });
-                }
+        if let Some(srcs) = &self.config.posts_src_scripts {
+            for src in srcs {
+                scripts.push(Script::Src {
+                    src: src.to_string(),
+                });
             }
-            None => (),
         }
 
         Ok(ScriptsTemplate {
@@ -284,31 +858,192 @@ impl<'a> Renderer<'a> {
     }
 }
 
+// An additional page from the content directory.
+struct ContentPage {
+    path: PathBuf,
+    // The file name, shown in the page title.
+    name: String,
+    // Used in place of the name in its path and URL.
+    slug: String,
+}
+
+// One page of a paginated list.
+struct Page<'p, T> {
+    items: &'p [T],
+    dest: PathBuf,
+    pagination: Pagination,
+}
+
+// Title and location of a feed, which covers either every post or a tag.
+struct FeedScope {
+    title: String,
+    description: String,
+    // Full URL of the page the feed follows.
+    link: String,
+    // Full URL of the feed itself.
+    feed_url: String,
+    // Date of the latest post.
+    updated: DateTime<FixedOffset>,
+}
+
+// Shared by the render workers, so this has to stay `Sync`.
 struct Markdown {
     syntax_set: SyntaxSet,
     theme: Theme,
-    options: Options,
 }
 
 impl Markdown {
     fn with_theme(theme: Theme) -> Self {
-        let mut options = Options::empty();
-        options.insert(Options::ENABLE_FOOTNOTES);
         Self {
             syntax_set: SyntaxSet::load_defaults_newlines(),
             theme,
-            options,
         }
     }
 
-    fn render_html(&self, content: &str) -> Result<String, Error> {
-        let parser = Parser::new_ext(content, self.options);
+    fn render_html(&self, content: &str, options: MarkdownOptions) -> Result<String, Error> {
+        let parser = Parser::new_ext(content, parser_options(options));
         let events = syntax_hl(parser, &self.syntax_set, &self.theme)?;
         let events = notes(events);
+        let events = headings(events, options.heading_anchors);
         let mut html = String::new();
         html::push_html(&mut html, events.into_iter());
         Ok(html)
     }
+
+    // Nested list of links to the headings of `content`, with the same ids
+    // as `render_html` gives them.
+    fn toc(&self, content: &str, options: MarkdownOptions) -> String {
+        let mut ids = HeadingIds::default();
+        let mut html = String::new();
+        // Levels of the lists still open.
+        let mut open: Vec<HeadingLevel> = Vec::new();
+        let mut heading: Option<Vec<Event<'_>>> = None;
+        for event in Parser::new_ext(content, parser_options(options)) {
+            match event {
+                Event::Start(Tag::Heading(..)) => heading = Some(Vec::new()),
+                Event::End(Tag::Heading(level, id, _)) => {
+                    let text = heading_text(&heading.take().unwrap_or_default());
+                    let id = ids.next(id, &text);
+                    while open.last().is_some_and(|&open| open > level) {
+                        html.push_str("</li></ul>");
+                        open.pop();
+                    }
+                    if open.last() == Some(&level) {
+                        html.push_str("</li>");
+                    } else {
+                        html.push_str("<ul>");
+                        open.push(level);
+                    }
+                    html.push_str(&format!(r##"<li><a href="#{}">"##, id));
+                    escape_html(&mut html, &text).unwrap();
+                    html.push_str("</a>");
+                }
+                event => {
+                    if let Some(inner) = &mut heading {
+                        inner.push(event);
+                    }
+                }
+            }
+        }
+        for _ in open {
+            html.push_str("</li></ul>");
+        }
+        html
+    }
+}
+
+fn parser_options(options: MarkdownOptions) -> Options {
+    let mut parser_options = Options::ENABLE_FOOTNOTES;
+    parser_options.set(Options::ENABLE_TABLES, options.tables);
+    parser_options.set(Options::ENABLE_STRIKETHROUGH, options.strikethrough);
+    parser_options.set(Options::ENABLE_TASKLISTS, options.tasklists);
+    parser_options.set(Options::ENABLE_SMART_PUNCTUATION, options.smart_punctuation);
+    parser_options.set(
+        Options::ENABLE_HEADING_ATTRIBUTES,
+        options.heading_attributes,
+    );
+    parser_options
+}
+
+// Unique ids for the headings of a document.
+#[derive(Default)]
+struct HeadingIds {
+    used: HashSet<String>,
+}
+
+impl HeadingIds {
+    // The heading's own `id` is used if it was given one, and otherwise
+    // one is made from its text. Either way, an id that is already taken
+    // gets a number added, and the id is safe to put in an attribute.
+    fn next(&mut self, id: Option<&str>, text: &str) -> String {
+        let base = match id {
+            Some(id) => {
+                let mut escaped = String::new();
+                escape_html(&mut escaped, id).unwrap();
+                escaped
+            }
+            None => match util::slugify(text) {
+                slug if slug.is_empty() => "section".to_string(),
+                slug => slug,
+            },
+        };
+        let mut id = base.clone();
+        let mut n = 0;
+        while !self.used.insert(id.clone()) {
+            n += 1;
+            id = format!("{}-{}", base, n);
+        }
+        id
+    }
+}
+
+// Give every heading an id from its text, and with `anchors`, a link to itself.
+fn headings(events: Vec<Event<'_>>, anchors: bool) -> Vec<Event<'_>> {
+    let mut result = Vec::new();
+    let mut ids = HeadingIds::default();
+    let mut heading: Option<Vec<Event<'_>>> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::Heading(..)) => heading = Some(Vec::new()),
+            Event::End(Tag::Heading(level, id, classes)) => {
+                let inner = heading.take().unwrap_or_default();
+                let id = ids.next(id, &heading_text(&inner));
+                let mut class = String::new();
+                if !classes.is_empty() {
+                    class.push_str(" class=\"");
+                    escape_html(&mut class, &classes.join(" ")).unwrap();
+                    class.push('"');
+                }
+                let open = format!(r#"<{} id="{}"{}>"#, level, id, class);
+                result.push(Event::Html(CowStr::Boxed(open.into_boxed_str())));
+                result.extend(inner);
+                if anchors {
+                    let anchor = format!(
+                        r##"<a class="heading-anchor" href="#{}" aria-label="Link to this section">#</a>"##,
+                        id
+                    );
+                    result.push(Event::Html(CowStr::Boxed(anchor.into_boxed_str())));
+                }
+                let close = format!("</{}>\n", level);
+                result.push(Event::Html(CowStr::Boxed(close.into_boxed_str())));
+            }
+            event => match &mut heading {
+                Some(inner) => inner.push(event),
+                None => result.push(event),
+            },
+        }
+    }
+    result
+}
+
+fn heading_text(events: &[Event<'_>]) -> String {
+    let mut text = String::new();
+    for event in events {
+        if let Event::Text(t) | Event::Code(t) = event {
+            text.push_str(t);
+        }
+    }
+    text
 }
 
 fn syntax_hl<'a>(
@@ -426,3 +1161,19 @@ fn collect_sidenotes(events: &[Event<'_>]) -> Vec<String> {
     sidenotes.reverse();
     sidenotes
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heading_ids_are_unique() {
+        let mut ids = HeadingIds::default();
+        assert_eq!(ids.next(None, "Intro"), "intro");
+        assert_eq!(ids.next(Some("intro"), "Other"), "intro-1");
+        assert_eq!(ids.next(None, "Intro"), "intro-2");
+        assert_eq!(ids.next(None, "Intro 1"), "intro-1-1");
+        assert_eq!(ids.next(Some("a\"b"), "Quoted"), "a&quot;b");
+        assert_eq!(ids.next(None, "!!!"), "section");
+    }
+}
@@ -0,0 +1,381 @@
+/// Local development server with live reload.
+use std::{
+    env, fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    process,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+};
+
+use crate::{
+    config::{BuildOptions, Config},
+    error::Error,
+    watch,
+};
+
+// Default port for `rite serve`.
+pub const DEFAULT_PORT: u16 = 8000;
+
+// Path of the server-sent events endpoint used for live reload.
+const RELOAD_PATH: &str = "/__rite/reload";
+
+// Injected before `</body>` in every served HTML page.
+const RELOAD_SCRIPT: &str =
+    r#"<script>new EventSource("/__rite/reload").onmessage = () => location.reload();</script>"#;
+
+/// Build the site into a temporary directory and serve it on localhost,
+/// rebuilding and reloading open pages whenever a source changes.
+/// Drafts and future posts are always included.
+/// The temporary directory is removed when the server stops.
+pub fn serve(config: &Config, port: u16) -> Result<(), Error> {
+    let site = Arc::new(Site::new(
+        env::temp_dir().join(format!("rite-serve-{}", process::id())),
+    ));
+    {
+        let site = Arc::clone(&site);
+        ctrlc::set_handler(move || {
+            println!(">> stopping");
+            site.remove();
+            process::exit(0);
+        })?;
+    }
+    let result = run(config, port, &site);
+    site.remove();
+    result
+}
+
+fn run(config: &Config, port: u16, site: &Arc<Site>) -> Result<(), Error> {
+    let options = BuildOptions {
+        drafts: true,
+        future: true,
+    };
+    site.build(config, options)?;
+
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!(">> serving '{}'", site.root.display());
+    println!(">> listening on http://127.0.0.1:{}/", port);
+
+    let reload = Arc::new(Reload::default());
+    {
+        let site = Arc::clone(site);
+        let reload = Arc::clone(&reload);
+        thread::spawn(move || accept(listener, &site, &reload));
+    }
+
+    watch::watch(config.clone(), |config, _| {
+        site.build(config, options)?;
+        reload.notify();
+        Ok(())
+    })
+}
+
+// The site is built into two directories in turn, so that pages are never
+// served from a directory that is being rebuilt. Each build brings the
+// unserved directory up to date and then swaps it in.
+struct Site {
+    root: PathBuf,
+    dirs: [PathBuf; 2],
+    served: Mutex<usize>,
+}
+
+impl Site {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            dirs: [root.join("a"), root.join("b")],
+            root,
+            // So that the first build goes into the first directory.
+            served: Mutex::new(1),
+        }
+    }
+
+    // Directory pages are currently served from.
+    fn served(&self) -> PathBuf {
+        self.dirs[*self.served.lock().unwrap()].clone()
+    }
+
+    fn build(&self, config: &Config, options: BuildOptions) -> Result<(), Error> {
+        let next = 1 - *self.served.lock().unwrap();
+        let mut config = config.clone();
+        config.build_root = self.dirs[next].clone();
+        crate::build(&config, options)?;
+        *self.served.lock().unwrap() = next;
+        Ok(())
+    }
+
+    fn remove(&self) {
+        if let Err(e) = fs::remove_dir_all(&self.root) {
+            if e.kind() != io::ErrorKind::NotFound {
+                eprintln!("failed to remove '{}': {}", self.root.display(), e);
+            }
+        }
+    }
+}
+
+// Shared rebuild counter, waited on by live reload connections.
+#[derive(Default)]
+struct Reload {
+    generation: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl Reload {
+    fn notify(&self) {
+        *self.generation.lock().unwrap() += 1;
+        self.changed.notify_all();
+    }
+
+    fn wait(&self, seen: u64) -> u64 {
+        let guard = self.generation.lock().unwrap();
+        *self.changed.wait_while(guard, |g| *g == seen).unwrap()
+    }
+
+    fn current(&self) -> u64 {
+        *self.generation.lock().unwrap()
+    }
+}
+
+fn accept(listener: TcpListener, site: &Arc<Site>, reload: &Arc<Reload>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let root = site.served();
+        let reload = Arc::clone(reload);
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &root, &reload) {
+                eprintln!("failed to handle request: {}", e);
+            }
+        });
+    }
+}
+
+fn handle(mut stream: TcpStream, root: &Path, reload: &Reload) -> Result<(), Error> {
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone()?);
+    reader.read_line(&mut request_line)?;
+    // Drain the headers; nothing in them is needed.
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return respond(&mut stream, "400 Bad Request", "text/plain", b"bad request"),
+    };
+    if method != "GET" {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"method not allowed",
+        );
+    }
+
+    let path = target.split(['?', '#']).next().unwrap_or("/");
+    if path == RELOAD_PATH {
+        return events(stream, reload);
+    }
+
+    match resolve(root, path) {
+        Some(file) => {
+            let body = fs::read(&file)?;
+            send_file(&mut stream, "200 OK", &file, body)
+        }
+        None => {
+            let file = root.join("404.html");
+            let body = fs::read(&file).unwrap_or_else(|_| b"not found".to_vec());
+            send_file(&mut stream, "404 Not Found", &file, body)
+        }
+    }
+}
+
+// Map a request path onto a file in the build directory.
+// Directories resolve to their `index.html`, and anything escaping the
+// root is treated as not found.
+fn resolve(root: &Path, path: &str) -> Option<PathBuf> {
+    let path = percent_decode(path);
+    let mut file = root.to_path_buf();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => file.push(part),
+            Component::CurDir => (),
+            _ => return None,
+        }
+    }
+    if file.is_dir() {
+        file.push("index.html");
+    }
+    file.is_file().then_some(file)
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn send_file(
+    stream: &mut TcpStream,
+    status: &str,
+    file: &Path,
+    body: Vec<u8>,
+) -> Result<(), Error> {
+    let content_type = content_type(file);
+    if content_type.starts_with("text/html") {
+        let html = String::from_utf8_lossy(&body);
+        let html = match html.rfind("</body>") {
+            Some(i) => format!("{}{}{}", &html[..i], RELOAD_SCRIPT, &html[i..]),
+            None => format!("{}{}", html, RELOAD_SCRIPT),
+        };
+        respond(stream, status, content_type, html.as_bytes())
+    } else {
+        respond(stream, status, content_type, &body)
+    }
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Result<(), Error> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    Ok(())
+}
+
+// Hold the connection open and send an event after every rebuild.
+fn events(mut stream: TcpStream, reload: &Reload) -> Result<(), Error> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-store\r\n\r\n"
+    )?;
+    stream.flush()?;
+    let mut seen = reload.current();
+    loop {
+        seen = reload.wait(seen);
+        // A write error means the page went away.
+        if stream.write_all(b"data: reload\n\n").is_err() {
+            return Ok(());
+        }
+    }
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    // A build directory inside `dir`, with a file beside it that must not
+    // be reachable.
+    fn site() -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("public");
+        fs::create_dir_all(root.join("posts")).unwrap();
+        fs::write(root.join("index.html"), "index").unwrap();
+        fs::write(root.join("posts").join("index.html"), "posts").unwrap();
+        fs::write(root.join("a page.html"), "page").unwrap();
+        fs::write(dir.path().join("secret"), "secret").unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn resolves_files() {
+        let (_dir, root) = site();
+        assert_eq!(resolve(&root, "/index.html"), Some(root.join("index.html")));
+        assert_eq!(
+            resolve(&root, "/a%20page.html"),
+            Some(root.join("a page.html"))
+        );
+    }
+
+    #[test]
+    fn resolves_directories_to_their_index() {
+        let (_dir, root) = site();
+        assert_eq!(resolve(&root, "/"), Some(root.join("index.html")));
+        assert_eq!(
+            resolve(&root, "/posts/"),
+            Some(root.join("posts").join("index.html"))
+        );
+        assert_eq!(
+            resolve(&root, "/posts"),
+            Some(root.join("posts").join("index.html"))
+        );
+    }
+
+    #[test]
+    fn unknown_paths_are_not_found() {
+        let (_dir, root) = site();
+        assert_eq!(resolve(&root, "/missing.html"), None);
+        assert_eq!(resolve(&root, "/posts/missing/"), None);
+    }
+
+    #[test]
+    fn paths_cannot_leave_the_root() {
+        let (_dir, root) = site();
+        assert_eq!(resolve(&root, "/../secret"), None);
+        assert_eq!(resolve(&root, "/posts/../../secret"), None);
+        assert_eq!(resolve(&root, "/%2e%2e/secret"), None);
+        assert_eq!(resolve(&root, "/posts/%2E%2E/%2e%2e/secret"), None);
+        assert_eq!(resolve(&root, "/..%2fsecret"), None);
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("/a%20b"), "/a b");
+        assert_eq!(percent_decode("/caf%C3%A9"), "/café");
+        // Incomplete or invalid escapes are left as they are.
+        assert_eq!(percent_decode("/100%"), "/100%");
+        assert_eq!(percent_decode("/%zz"), "/%zz");
+    }
+}
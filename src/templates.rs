@@ -1,8 +1,36 @@
-use std::path::Path;
+use std::{collections::BTreeMap, path::Path};
 
 use askama::Template;
+use serde::Serialize;
 
-use crate::post::{Post, Posts};
+use crate::{
+    config::{Feed, RobotsRule},
+    post::{Post, SeriesEntry, TagEntry},
+};
+
+/// Sources of every template, since they are compiled in.
+/// Changing any of them invalidates the outputs of previous builds.
+pub const SOURCES: &[&str] = &[
+    include_str!("../templates/404.html"),
+    include_str!("../templates/archive.html"),
+    include_str!("../templates/archive_period.html"),
+    include_str!("../templates/atom.xml"),
+    include_str!("../templates/base.html"),
+    include_str!("../templates/content.html"),
+    include_str!("../templates/index.html"),
+    include_str!("../templates/pagination.html"),
+    include_str!("../templates/post.html"),
+    include_str!("../templates/posts.html"),
+    include_str!("../templates/robots.txt"),
+    include_str!("../templates/rss.xml"),
+    include_str!("../templates/scripts.html"),
+    include_str!("../templates/search.html"),
+    include_str!("../templates/series.html"),
+    include_str!("../templates/series_list.html"),
+    include_str!("../templates/sitemap.xml"),
+    include_str!("../templates/tag.html"),
+    include_str!("../templates/tags.html"),
+];
 
 mod filters {
     use std::path::Path;
@@ -10,6 +38,16 @@ mod filters {
     pub fn path(path: &Path) -> askama::Result<String> {
         Ok(path.display().to_string().trim_end_matches('/').to_string())
     }
+
+    // Name of the page for a tag.
+    pub fn slug(tag: &str) -> askama::Result<String> {
+        Ok(crate::util::slugify(tag))
+    }
+
+    // Split any `]]>` so that `s` can sit inside a CDATA section.
+    pub fn cdata(s: &str) -> askama::Result<String> {
+        Ok(s.replace("]]>", "]]]]><![CDATA[>"))
+    }
 }
 
 #[derive(Template)]
@@ -29,12 +67,123 @@ pub struct IndexTemplate<'a> {
 pub struct RssTemplate<'a> {
     // RSS title.
     pub title: &'a str,
+    // Full URL of the page the feed follows.
+    pub link: &'a str,
+    // Full URL of the feed itself.
+    pub feed_url: &'a str,
     // Full base URL for the posts.
     pub posts_url: &'a str,
     // RSS description.
     pub description: &'a str,
+    // Feed language.
+    pub language: &'a str,
+    // Feed author, if configured.
+    pub author: Option<&'a str>,
+    // Time of the latest change to the feed, RFC 2822.
+    pub last_build_date: &'a str,
+    // Whether items include the full post, not just the summary.
+    pub full_content: bool,
+    // Posts to be included.
+    pub posts: &'a [&'a Post],
+}
+
+/// Atom feed template for posts.
+#[derive(Template)]
+#[template(path = "atom.xml")]
+pub struct AtomTemplate<'a> {
+    // Feed title.
+    pub title: &'a str,
+    // Full URL of the page the feed follows.
+    pub link: &'a str,
+    // Full URL of the feed itself.
+    pub feed_url: &'a str,
+    // Full base URL for the posts.
+    pub posts_url: &'a str,
+    // Feed language.
+    pub language: &'a str,
+    // Feed author name.
+    pub author: &'a str,
+    // Time of the latest change to the feed, RFC 3339.
+    pub updated: &'a str,
+    // Whether entries include the full post, not just the summary.
+    pub full_content: bool,
     // Posts to be included.
-    pub posts: &'a Posts,
+    pub posts: &'a [&'a Post],
+}
+
+/// JSON Feed for posts, serialized rather than templated.
+///
+/// <https://www.jsonfeed.org/version/1.1/>
+#[derive(Serialize)]
+pub struct JsonFeed<'a> {
+    pub version: &'static str,
+    pub title: &'a str,
+    pub home_page_url: &'a str,
+    pub feed_url: &'a str,
+    pub description: &'a str,
+    pub language: &'a str,
+    pub authors: [JsonFeedAuthor<'a>; 1],
+    pub items: Vec<JsonFeedItem<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct JsonFeedAuthor<'a> {
+    pub name: &'a str,
+}
+
+#[derive(Serialize)]
+pub struct JsonFeedItem<'a> {
+    pub id: String,
+    pub url: String,
+    pub title: &'a str,
+    // Plain text.
+    pub summary: String,
+    pub content_html: &'a str,
+    // RFC 3339.
+    pub date_published: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_modified: Option<String>,
+    pub tags: &'a [String],
+}
+
+/// Sitemap of every page, for search engines.
+#[derive(Template)]
+#[template(path = "sitemap.xml")]
+pub struct SitemapTemplate<'a> {
+    pub urls: &'a [SitemapUrl],
+}
+
+#[derive(Hash)]
+pub struct SitemapUrl {
+    // Full URL of the page.
+    pub loc: String,
+    // Date of the latest change to the page, RFC 3339.
+    pub lastmod: Option<String>,
+}
+
+/// Crawler rules, pointing to the sitemap.
+#[derive(Template)]
+#[template(path = "robots.txt")]
+pub struct RobotsTemplate<'a> {
+    pub rules: &'a [RobotsRule],
+    // Full URL of the sitemap.
+    pub sitemap_url: &'a str,
+}
+
+/// Index of words in posts, searched by the search page's script.
+#[derive(Serialize)]
+pub struct SearchIndex<'a> {
+    pub posts: Vec<SearchPost<'a>>,
+    // Each word with the indices of the posts it appears in.
+    pub terms: BTreeMap<String, Vec<usize>>,
+}
+
+#[derive(Serialize)]
+pub struct SearchPost<'a> {
+    pub title: &'a str,
+    pub url: String,
+    pub date: String,
+    pub tags: &'a [String],
 }
 
 /// Individual post template.
@@ -45,23 +194,51 @@ pub struct PostTemplate<'a> {
     pub title: &'a str,
     // Posts root directory.
     pub posts_root: &'a Path,
+    // Feeds to link to.
+    pub feeds: &'a [Feed],
     // Post to be rendered.
     pub post: &'a Post,
+    // The posts before and after it.
+    pub prev: Option<&'a Post>,
+    pub next: Option<&'a Post>,
+    // Posts sharing its tags.
+    pub related: &'a [&'a Post],
+    // The series it is part of.
+    pub series: Option<SeriesNav<'a>>,
+    // Table of contents, if the post asks for one.
+    pub toc: Option<&'a str>,
     // Additional scripts.
     // Used for scripts needed only for posts.
     pub scripts: &'a ScriptsTemplate,
 }
 
+/// Where a post sits in its series.
+pub struct SeriesNav<'a> {
+    pub name: &'a str,
+    // Name of the series in paths and URLs.
+    pub slug: &'a str,
+    // Position of the post, from 1.
+    pub part: usize,
+    // Every part, in reading order.
+    pub parts: Vec<&'a Post>,
+}
+
 /// Posts index template.
 #[derive(Template)]
 #[template(path = "posts.html")]
 pub struct PostsTemplate<'a> {
     // Document (base) title element.
     pub title: &'a str,
+    // Feeds to link to.
+    pub feeds: &'a [Feed],
+    // Posts root directory.
+    pub posts_root: &'a Path,
     // Description about the blog.
     pub description: &'a str,
-    // Posts to be included.
-    pub posts: &'a Posts,
+    // Posts on this page.
+    pub posts: &'a [Post],
+    // Position of this page among the others.
+    pub pagination: &'a Pagination,
 }
 
 /// Post tag template.
@@ -70,10 +247,103 @@ pub struct PostsTemplate<'a> {
 pub struct TagTemplate<'a> {
     // Document (base) title element.
     pub title: &'a str,
+    // Feeds to link to.
+    pub feeds: &'a [Feed],
+    // Posts root directory.
+    pub posts_root: &'a Path,
     // Name of the tag.
     pub name: &'a str,
-    // Posts to be searched for tags.
-    pub posts: &'a Posts,
+    // Name of the tag in paths and URLs.
+    pub slug: &'a str,
+    // Posts with the tag on this page.
+    pub posts: &'a [&'a Post],
+    // Position of this page among the others.
+    pub pagination: &'a Pagination,
+}
+
+/// Position of a page in a list split over several, for the posts index
+/// and tag pages.
+#[derive(Hash)]
+pub struct Pagination {
+    // Page number, from 1.
+    pub page: usize,
+    pub pages: usize,
+    // URLs of the neighbouring pages, newer then older.
+    pub prev: Option<String>,
+    pub next: Option<String>,
+}
+
+/// Series index template.
+#[derive(Template)]
+#[template(path = "series_list.html")]
+pub struct SeriesListTemplate<'a> {
+    // Document (base) title element.
+    pub title: &'a str,
+    // Posts root directory.
+    pub posts_root: &'a Path,
+    // Every series, sorted by name.
+    pub series: &'a [SeriesEntry],
+}
+
+/// Template for the parts of one series.
+#[derive(Template)]
+#[template(path = "series.html")]
+pub struct SeriesTemplate<'a> {
+    // Document (base) title element.
+    pub title: &'a str,
+    // Posts root directory.
+    pub posts_root: &'a Path,
+    // Name of the series.
+    pub name: &'a str,
+    // Parts, in reading order.
+    pub posts: &'a [&'a Post],
+}
+
+/// Archive overview template, counting posts by year and month.
+#[derive(Template)]
+#[template(path = "archive.html")]
+pub struct ArchiveTemplate<'a> {
+    // Document (base) title element.
+    pub title: &'a str,
+    // Posts root directory.
+    pub posts_root: &'a Path,
+    // Years with posts, newest first.
+    pub years: &'a [ArchiveYear],
+}
+
+/// Archive page template for the posts of a year or month.
+#[derive(Template)]
+#[template(path = "archive_period.html")]
+pub struct ArchivePeriodTemplate<'a> {
+    // Document (base) title element.
+    pub title: &'a str,
+    // Posts root directory.
+    pub posts_root: &'a Path,
+    // Name of the year or month.
+    pub name: &'a str,
+    // Months of the year with posts, empty for a month.
+    pub months: &'a [ArchiveMonth],
+    // Posts from the year or month.
+    pub posts: &'a [&'a Post],
+}
+
+#[derive(Hash)]
+pub struct ArchiveYear {
+    pub name: String,
+    // Path in the archive directory.
+    pub path: String,
+    pub count: usize,
+    // Months with posts, newest first.
+    pub months: Vec<ArchiveMonth>,
+}
+
+#[derive(Hash)]
+pub struct ArchiveMonth {
+    // Name with the year, such as "May 2023".
+    pub name: String,
+    // Path in the archive directory.
+    pub path: String,
+    pub count: usize,
 }
 
 /// Post tags index template.
@@ -84,8 +354,20 @@ pub struct TagsTemplate<'a> {
     pub title: &'a str,
     // Posts root directory.
     pub posts_root: &'a Path,
-    // List of tags.
-    pub tags: &'a [&'a str],
+    // Tags, the most used first.
+    pub tags: &'a [&'a TagEntry],
+}
+
+/// Search page template.
+#[derive(Template)]
+#[template(path = "search.html")]
+pub struct SearchTemplate<'a> {
+    // Document (base) title element.
+    pub title: &'a str,
+    // Posts root directory.
+    pub posts_root: &'a Path,
+    // Text above the search box.
+    pub description: &'a str,
 }
 
 /// 404 not found template.
@@ -111,7 +393,7 @@ pub struct ContentTemplate<'a> {
 }
 
 /// A list of script elements, with an optional noscript element.
-#[derive(Template)]
+#[derive(Hash, Template)]
 #[template(path = "scripts.html")]
 pub struct ScriptsTemplate {
     pub scripts: Vec<Script>,
@@ -119,6 +401,7 @@ pub struct ScriptsTemplate {
 }
 
 /// Simple script element.
+#[derive(Hash)]
 pub enum Script {
     /// An embedded script.
     Embed { contents: String },
@@ -126,3 +409,18 @@ pub enum Script {
     /// Uses `async`.
     Src { src: String },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::filters;
+
+    #[test]
+    fn cdata_splits_section_ends() {
+        assert_eq!(filters::cdata("a]]>b").unwrap(), "a]]]]><![CDATA[>b");
+        assert_eq!(
+            filters::cdata("]]>]]>").unwrap(),
+            "]]]]><![CDATA[>]]]]><![CDATA[>"
+        );
+        assert_eq!(filters::cdata("a]]b>").unwrap(), "a]]b>");
+    }
+}
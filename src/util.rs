@@ -1,14 +1,18 @@
 use std::{
     fs,
+    hash::{Hash, Hasher},
     path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
 };
 
-use crate::error::Error;
+use crate::{error::Error, manifest::Manifest};
 
 /// Copy static files recursively from `from` to `to`.
+/// Files unchanged since the last build are skipped.
 ///
 /// <https://stackoverflow.com/questions/26958489/>
-pub(crate) fn copy_static(from: &Path, to: &Path) -> Result<(), Error> {
+pub(crate) fn copy_static(from: &Path, to: &Path, manifest: &Manifest) -> Result<(), Error> {
     let from_skip = from.components().count();
     let mut stack = vec![from.to_path_buf()];
     while let Some(curr) = stack.pop() {
@@ -24,8 +28,9 @@ pub(crate) fn copy_static(from: &Path, to: &Path) -> Result<(), Error> {
             } else {
                 match path.file_name() {
                     Some(name) => {
-                        println!("  -- {}", path.display());
-                        fs::copy(&path, &dest.join(name))?;
+                        if manifest.copy(&path, &dest.join(name))? {
+                            println!("  -- {}", path.display());
+                        }
                     }
                     None => unreachable!(),
                 }
@@ -34,3 +39,223 @@ pub(crate) fn copy_static(from: &Path, to: &Path) -> Result<(), Error> {
     }
     Ok(())
 }
+
+/// Whether `path` is a hidden file, or an editor's swap, backup or lock
+/// file, none of which are sources.
+pub(crate) fn is_ignored(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    name.starts_with(['.', '#'])
+        || name.ends_with('~')
+        || [".swp", ".swo", ".swx", ".tmp", ".bak"]
+            .iter()
+            .any(|ext| name.ends_with(ext))
+}
+
+/// Whether `path` is a markdown source, a `.md` file that is not ignored.
+pub(crate) fn is_markdown(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext == "md") && !is_ignored(path)
+}
+
+/// Turn `s` into something fit for a file name or URL: lowercase ASCII
+/// letters and digits separated by single dashes.
+/// Anything else is transliterated to ASCII first, so `Ünïcödé` becomes
+/// `unicode`, and slugs never need percent-encoding.
+pub(crate) fn slugify(s: &str) -> String {
+    let mut slug = String::new();
+    for c in deunicode::deunicode(s).chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// The text of rendered `html`, without tags and with whitespace collapsed.
+pub(crate) fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => (),
+        }
+    }
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Rewrite relative `href` and `src` attributes in rendered `html` to
+/// absolute URLs, for HTML that is read away from the site.
+/// Paths starting with `/` are taken from `site_url`, anything else
+/// relative from `page_url`.
+pub(crate) fn absolute_urls(html: &str, site_url: &str, page_url: &str) -> String {
+    let page_dir = page_url.rsplit_once('/').map_or(page_url, |(dir, _)| dir);
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some((start, len)) = [" href=\"", " src=\""]
+        .iter()
+        .filter_map(|attr| rest.find(attr).map(|i| (i, attr.len())))
+        .min()
+    {
+        let (before, after) = rest.split_at(start + len);
+        out.push_str(before);
+        let end = after.find('"').unwrap_or(after.len());
+        let url = &after[..end];
+        let scheme = url
+            .find([':', '/', '?', '#'])
+            .is_some_and(|i| url[i..].starts_with(':'));
+        if scheme || url.starts_with("//") || url.is_empty() {
+            out.push_str(url);
+        } else if url.starts_with('/') {
+            out.push_str(site_url.trim_end_matches('/'));
+            out.push_str(url);
+        } else if url.starts_with('#') {
+            out.push_str(page_url);
+            out.push_str(url);
+        } else {
+            out.push_str(page_dir);
+            out.push('/');
+            out.push_str(url);
+        }
+        rest = &after[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Map `f` over `items` with a pool of worker threads, one per available
+/// core. Results are returned in the order of `items`, regardless of which
+/// worker finished first.
+pub(crate) fn par_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(items.len());
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        match items.get(i) {
+                            Some(item) => done.push((i, f(item))),
+                            None => return done,
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            for (i, result) in handle.join().expect("render worker panicked") {
+                results[i] = Some(result);
+            }
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.expect("missing render result"))
+        .collect()
+}
+
+/// Hash `value` with FNV-1a, which unlike the standard library's hasher is
+/// stable between runs, so the result can be persisted.
+pub(crate) fn hash(value: &(impl Hash + ?Sized)) -> String {
+    let mut hasher = Fnv(FNV_OFFSET_BASIS);
+    value.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SITE: &str = "https://example.com/";
+    const PAGE: &str = "https://example.com/posts/a-post.html";
+
+    fn absolute(html: &str) -> String {
+        absolute_urls(html, SITE, PAGE)
+    }
+
+    #[test]
+    fn absolute_urls_keep_urls_with_a_scheme() {
+        let html = r#"<a href="https://other.org/a">a</a><a href="mailto:me@example.com">b</a>"#;
+        assert_eq!(absolute(html), html);
+    }
+
+    #[test]
+    fn absolute_urls_keep_protocol_relative_urls() {
+        let html = r#"<script src="//cdn.example.org/a.js"></script>"#;
+        assert_eq!(absolute(html), html);
+    }
+
+    #[test]
+    fn absolute_urls_keep_empty_urls() {
+        let html = r#"<a href="">a</a>"#;
+        assert_eq!(absolute(html), html);
+    }
+
+    #[test]
+    fn absolute_urls_resolve_fragments_against_the_page() {
+        assert_eq!(
+            absolute(r##"<a href="#note-1">1</a>"##),
+            r##"<a href="https://example.com/posts/a-post.html#note-1">1</a>"##
+        );
+    }
+
+    #[test]
+    fn absolute_urls_resolve_root_relative_urls_against_the_site() {
+        assert_eq!(
+            absolute(r#"<img src="/static/a.png">"#),
+            r#"<img src="https://example.com/static/a.png">"#
+        );
+    }
+
+    #[test]
+    fn absolute_urls_resolve_page_relative_urls_against_the_page() {
+        assert_eq!(
+            absolute(r#"<a href="other.html">a</a><img src="img/a:b.png">"#),
+            r#"<a href="https://example.com/posts/other.html">a</a><img src="https://example.com/posts/img/a:b.png">"#
+        );
+    }
+
+    #[test]
+    fn absolute_urls_leave_other_attributes() {
+        let html = r#"<a data-href="other.html" title="src=x">a</a>"#;
+        assert_eq!(absolute(html), html);
+    }
+}
@@ -0,0 +1,225 @@
+/// Watch mode, rebuilding the parts of the site affected by a change.
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    config::{self, BuildOptions, Config},
+    error::Error,
+    manifest::Manifest,
+    render::Renderer,
+    util, STATIC_FILES_PATH,
+};
+
+// Templates are compiled in, so changes to them can only be reported.
+const TEMPLATES_PATH: &str = "templates";
+
+// How often the sources are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// How long the sources need to be quiet before a rebuild starts,
+// so that a burst of saves results in a single rebuild.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+// Modification times of every file under the watched paths.
+type Snapshot = HashMap<PathBuf, SystemTime>;
+
+/// Watch the sources in `config` and call `rebuild` with whatever changed.
+/// The configuration file is reloaded first if it is among the changes.
+/// Errors are reported and watching continues.
+pub fn watch(
+    mut config: Config,
+    mut rebuild: impl FnMut(&Config, &Changes) -> Result<(), Error>,
+) -> ! {
+    println!(">> watching for changes");
+    let mut current = snapshot(&sources(&config));
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let mut latest = snapshot(&sources(&config));
+        let mut changed = diff(&current, &latest);
+        if changed.is_empty() {
+            continue;
+        }
+
+        // Wait for the burst of changes to settle, starting from the
+        // snapshot the first changes were found in so none are missed.
+        loop {
+            thread::sleep(DEBOUNCE);
+            let next = snapshot(&sources(&config));
+            let more = diff(&latest, &next);
+            if more.is_empty() {
+                break;
+            }
+            changed.extend(more);
+            latest = next;
+        }
+        current = latest;
+
+        let changes = Changes::classify(&config, &changed);
+        if changes.templates {
+            println!(">> templates changed, restart to pick them up");
+        }
+        if changes.config {
+            println!(">> configuration changed, reloading");
+            match config::read_config() {
+                Ok(new) => {
+                    config = new;
+                    current = snapshot(&sources(&config));
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    continue;
+                }
+            }
+        }
+
+        if let Err(e) = rebuild(&config, &changes) {
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// Sources affected by a set of changed paths.
+#[derive(Default)]
+pub struct Changes {
+    config: bool,
+    static_files: bool,
+    posts: bool,
+    index: bool,
+    not_found: bool,
+    search: bool,
+    content: bool,
+    templates: bool,
+    removed: bool,
+}
+
+impl Changes {
+    fn classify(config: &Config, paths: &[PathBuf]) -> Self {
+        let mut changes = Self::default();
+        let scripts = config.posts_embed_scripts.as_deref();
+        for path in paths {
+            changes.removed |= !path.exists();
+            if path == &config::config_path() {
+                changes.config = true;
+            } else if path.starts_with(&config.posts)
+                || scripts.is_some_and(|s| path.starts_with(s))
+            {
+                changes.posts = true;
+            } else if path.starts_with(&config.content) {
+                match path.file_stem().and_then(|s| s.to_str()) {
+                    Some("index") => changes.index = true,
+                    Some("404") => changes.not_found = true,
+                    Some("search") => changes.search = true,
+                    // The posts description lives in the content directory.
+                    Some("posts") => changes.posts = true,
+                    _ => changes.content = true,
+                }
+            } else if path.starts_with(STATIC_FILES_PATH) {
+                changes.static_files = true;
+            } else if path.starts_with(TEMPLATES_PATH) {
+                changes.templates = true;
+            }
+        }
+        changes
+    }
+}
+
+/// Rebuild the parts of the site in `config.build_root` affected by
+/// `changes`.
+pub fn rebuild(config: &Config, options: BuildOptions, changes: &Changes) -> Result<(), Error> {
+    // Anything could have moved, or outputs may need pruning,
+    // so run a complete build.
+    if changes.config || changes.removed {
+        return crate::build(config, options);
+    }
+
+    let manifest = Manifest::load(config)?;
+    // Anything written is recorded even if the rebuild fails.
+    let result = render_changes(config, options, changes, &manifest);
+    manifest.finish(false)?;
+    result
+}
+
+fn render_changes(
+    config: &Config,
+    options: BuildOptions,
+    changes: &Changes,
+    manifest: &Manifest,
+) -> Result<(), Error> {
+    if changes.static_files {
+        crate::copy_static(config, manifest)?;
+    }
+
+    if changes.index || changes.not_found || changes.search || changes.content || changes.posts {
+        let renderer = Renderer::new(config, options, manifest)?;
+        if changes.index {
+            renderer.render_index()?;
+        }
+        if changes.not_found {
+            renderer.render_not_found()?;
+        }
+        if changes.search {
+            renderer.render_search()?;
+        }
+        if changes.content {
+            renderer.render_content()?;
+        }
+        if changes.content || changes.posts {
+            let mut posts = renderer.published_posts()?;
+            if changes.posts {
+                renderer.render_posts(&mut posts)?;
+            }
+            renderer.render_sitemap(&posts)?;
+        }
+    }
+    Ok(())
+}
+
+fn sources(config: &Config) -> Vec<PathBuf> {
+    let mut sources = vec![
+        config::config_path(),
+        config.content.clone(),
+        config.posts.clone(),
+        PathBuf::from(STATIC_FILES_PATH),
+        PathBuf::from(TEMPLATES_PATH),
+    ];
+    if let Some(path) = &config.posts_embed_scripts {
+        sources.push(path.clone());
+    }
+    sources
+}
+
+fn snapshot(paths: &[PathBuf]) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    let mut stack = paths.to_vec();
+    while let Some(path) = stack.pop() {
+        // Editors write these while a file is being edited.
+        if util::is_ignored(&path) {
+            continue;
+        }
+        if path.is_dir() {
+            if let Ok(entries) = path.read_dir() {
+                stack.extend(entries.flatten().map(|entry| entry.path()));
+            }
+        } else if let Ok(mtime) = path.metadata().and_then(|m| m.modified()) {
+            snapshot.insert(path, mtime);
+        }
+    }
+    snapshot
+}
+
+// Paths added, removed, or modified between two snapshots.
+fn diff(old: &Snapshot, new: &Snapshot) -> Vec<PathBuf> {
+    let modified = new
+        .iter()
+        .filter(|(path, mtime)| old.get(*path) != Some(mtime))
+        .map(|(path, _)| path);
+    let removed = old.keys().filter(|path| !new.contains_key(*path));
+    modified
+        .chain(removed)
+        .map(|path| path.to_path_buf())
+        .collect()
+}
@@ -0,0 +1,61 @@
+// Search posts with the index written by `rite build`.
+(function () {
+  const input = document.getElementById("search-input");
+  const results = document.getElementById("search-results");
+  let index = null;
+
+  // Same splitting as the index.
+  function terms(text) {
+    return text.toLowerCase().split(/[^\p{L}\p{N}]+/u).filter((term) => term);
+  }
+
+  // Posts matching every query term, as a prefix of an indexed term.
+  function matches(query) {
+    let found = null;
+    for (const q of query) {
+      const posts = new Set();
+      for (const [term, ids] of Object.entries(index.terms)) {
+        if (term.startsWith(q)) {
+          ids.forEach((id) => posts.add(id));
+        }
+      }
+      found = found === null ? posts : new Set([...found].filter((id) => posts.has(id)));
+    }
+    // Keep the index order, newest first.
+    return [...found].sort((a, b) => a - b).map((id) => index.posts[id]);
+  }
+
+  function search() {
+    results.replaceChildren();
+    const query = terms(input.value);
+    if (index === null || query.length === 0) {
+      return;
+    }
+    const found = matches(query);
+    if (found.length === 0) {
+      results.textContent = "No posts found.";
+      return;
+    }
+    for (const post of found) {
+      const item = document.createElement("div");
+      const date = document.createElement("span");
+      date.className = "post-date";
+      date.textContent = post.date;
+      const link = document.createElement("a");
+      link.className = "post-link";
+      link.href = post.url;
+      link.textContent = post.title;
+      item.append(date, " :: ", link);
+      results.append(item);
+    }
+  }
+
+  input.value = new URLSearchParams(location.search).get("q") || "";
+  input.addEventListener("input", search);
+  fetch(input.dataset.index)
+    .then((response) => response.json())
+    .then((loaded) => {
+      index = loaded;
+      search();
+    });
+})();